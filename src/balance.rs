@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use log::warn;
+
+use crate::{rent::minimum_balance, rpc::*, store::AccountStore, write_set::WriteSet};
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// `1.5` and `1.5sol` are SOL, `1500lamports` is lamports
+pub fn parse_amount(amount: &str) -> Result<u64> {
+    let amount = amount.trim().to_lowercase();
+
+    if let Some(lamports) = amount.strip_suffix("lamports") {
+        return lamports
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("{} is not a whole number of lamports", lamports.trim()));
+    }
+
+    let sol = amount.strip_suffix("sol").unwrap_or(&amount).trim();
+    let (whole, fraction) = sol.split_once('.').unwrap_or((sol, ""));
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        anyhow::bail!("{} is not a valid amount of SOL", sol);
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole
+            .parse()
+            .map_err(|_| anyhow!("{} is not a valid amount of SOL", sol))?
+    };
+    // pad to 9 digits so 0.5 becomes 500000000 lamports
+    let fraction: u64 = format!("{:0<9}", fraction).parse()?;
+
+    whole
+        .checked_mul(LAMPORTS_PER_SOL)
+        .and_then(|lamports| lamports.checked_add(fraction))
+        .ok_or_else(|| anyhow!("{} SOL is too much", sol))
+}
+
+/// Sets the lamports of every account, keeping its data, owner and executable flag.
+/// Accounts that don't exist are created as system wallets
+pub async fn set_balances<S: AccountStore>(
+    store: &S,
+    pubkeys: &[String],
+    lamports: u64,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    if lamports == 0 {
+        anyhow::bail!("A balance of 0 would delete the accounts");
+    }
+
+    let mut write_set = WriteSet::new();
+    for (pubkey, account) in pubkeys.iter().zip(store.get_many(pubkeys).await?) {
+        let info = match account {
+            Some(account) => {
+                if lamports < minimum_balance(account.data.len()) {
+                    warn!(
+                        "{} has {} bytes of data and won't be rent exempt with {} lamports",
+                        pubkey,
+                        account.data.len(),
+                        lamports
+                    );
+                }
+                SetAccountInfo {
+                    lamports,
+                    ..account.to_set_account_info()?
+                }
+            }
+            None => SetAccountInfo {
+                data: Some(String::new()),
+                executable: false,
+                lamports,
+                owner: SYSTEM_PROGRAM_ID.to_string(),
+                rent_epoch: 0,
+            },
+        };
+
+        println!(
+            "{}: {} SOL",
+            pubkey,
+            lamports as f64 / LAMPORTS_PER_SOL as f64
+        );
+        write_set.set(pubkey, info);
+    }
+
+    write_set.apply(store, snapshot).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAccount, FakeSurfnet};
+
+    #[test]
+    fn parses_sol_and_lamports() {
+        assert_eq!(parse_amount("2").unwrap(), 2 * LAMPORTS_PER_SOL);
+        assert_eq!(parse_amount("1.5sol").unwrap(), 1_500_000_000);
+        assert_eq!(parse_amount(".000000001 SOL").unwrap(), 1);
+        assert_eq!(parse_amount("1500lamports").unwrap(), 1500);
+        assert!(parse_amount("0.0000000001").is_err());
+        assert!(parse_amount("1.5lamports").is_err());
+        assert!(parse_amount("lots").is_err());
+    }
+
+    #[tokio::test]
+    async fn keeps_data_and_creates_wallets() {
+        let surfnet = FakeSurfnet::start().await;
+        let existing = FakeAccount::new("SomeProgram", vec![1, 2, 3]);
+        surfnet.set_account("existing", existing.clone());
+
+        set_balances(
+            &surfnet.rpc(),
+            &["existing".to_string(), "new".to_string()],
+            5 * LAMPORTS_PER_SOL,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let funded = surfnet.account("existing").unwrap();
+        assert_eq!(funded.lamports, 5 * LAMPORTS_PER_SOL);
+        assert_eq!(funded.data, existing.data);
+        assert_eq!(funded.owner, existing.owner);
+
+        let wallet = surfnet.account("new").unwrap();
+        assert_eq!(wallet.lamports, 5 * LAMPORTS_PER_SOL);
+        assert_eq!(wallet.owner, SYSTEM_PROGRAM_ID);
+        assert!(wallet.data.is_empty());
+    }
+}
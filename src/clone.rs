@@ -0,0 +1,160 @@
+use std::{collections::HashSet, path::Path, str::FromStr};
+
+use anyhow::Result;
+use log::{info, warn};
+use mpl_core::types::{Key, UpdateAuthority};
+use mpl_token_metadata::accounts::{MasterEdition, Metadata, TokenRecord};
+use solana_pubkey::Pubkey;
+use spl_token::solana_program::program_pack::Pack;
+
+use crate::{
+    mpl::deser_asset_header,
+    pnft::{deser_ata, deser_metadata},
+    rpc::*,
+    store::AccountStore,
+    write_set::WriteSet,
+};
+
+/// Accounts that go with this one: the collection of a core asset, the mint and token record of a token account,
+/// the metadata and edition of a mint and the mint and collection of a metadata
+fn related_accounts(pubkey: &str, account: &GetAccountInfoResponse) -> Result<Vec<String>> {
+    let data = &account.data;
+    let mut related = Vec::new();
+
+    if account.owner == mpl_core::ID.to_string() {
+        if data.first() == Some(&(Key::AssetV1 as u8))
+            && let UpdateAuthority::Collection(collection) =
+                deser_asset_header(data)?.update_authority
+        {
+            related.push(collection.to_string());
+        }
+    } else if account.owner == spl_token::ID.to_string() {
+        if data.len() == spl_token::state::Account::LEN {
+            let token_account = deser_ata(data)?;
+            let mint = Pubkey::new_from_array(token_account.mint.to_bytes());
+            let token_key = Pubkey::from_str(pubkey)?;
+            related.push(mint.to_string());
+            // only pNFTs have one, missing ones are skipped
+            related.push(TokenRecord::find_pda(&mint, &token_key).0.to_string());
+        } else if data.len() == spl_token::state::Mint::LEN {
+            let mint = Pubkey::from_str(pubkey)?;
+            related.push(Metadata::find_pda(&mint).0.to_string());
+            related.push(MasterEdition::find_pda(&mint).0.to_string());
+        }
+    } else if account.owner == mpl_token_metadata::ID.to_string()
+        && data.first() == Some(&(mpl_token_metadata::types::Key::MetadataV1 as u8))
+    {
+        let metadata = deser_metadata(data)?;
+        related.push(metadata.mint.to_string());
+        if let Some(collection) = metadata.collection {
+            related.push(collection.key.to_string());
+        }
+    }
+
+    Ok(related)
+}
+
+/// Copies the accounts from `source` into `target` byte for byte.
+/// With `follow`, related accounts (see `related_accounts`) are cloned too, recursively
+pub async fn clone_accounts<S: AccountStore>(
+    source: &Rpc,
+    target: &S,
+    pubkeys: &[String],
+    follow: bool,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let mut write_set = WriteSet::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut pending: Vec<String> = pubkeys
+        .iter()
+        .filter(|pubkey| seen.insert(pubkey.to_string()))
+        .cloned()
+        .collect();
+    // the keys that were asked for have to exist, followed ones are best effort
+    let mut requested = true;
+
+    while !pending.is_empty() {
+        let accounts = source.get_multiple_accounts(&pending).await?;
+        let mut next = Vec::new();
+
+        for (pubkey, account) in pending.iter().zip(accounts) {
+            let Some(account) = account else {
+                if requested {
+                    anyhow::bail!("{} does not exist on {}", pubkey, source.url);
+                }
+                info!("{} does not exist on {}, skipping", pubkey, source.url);
+                continue;
+            };
+
+            if follow {
+                match related_accounts(pubkey, &account) {
+                    Ok(related) => {
+                        next.extend(related.into_iter().filter(|key| seen.insert(key.clone())))
+                    }
+                    Err(err) => warn!("Could not find accounts related to {}: {:#}", pubkey, err),
+                }
+            }
+
+            println!(
+                "Cloning {} ({} bytes, owned by {})",
+                pubkey,
+                account.data.len(),
+                account.owner
+            );
+            write_set.set(pubkey, account.to_set_account_info()?);
+        }
+
+        pending = next;
+        requested = false;
+    }
+
+    write_set.apply(target, snapshot).await?;
+    println!("Cloned {} accounts", write_set.pubkeys().len());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAccount, FakeSurfnet};
+
+    #[tokio::test]
+    async fn clones_accounts_verbatim() {
+        let source = FakeSurfnet::start().await;
+        let target = FakeSurfnet::start().await;
+        let account = FakeAccount::new(spl_token::ID, vec![1, 2, 3]);
+        source.set_account("wallet", account.clone());
+
+        clone_accounts(
+            &source.rpc(),
+            &target.rpc(),
+            &["wallet".to_string()],
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(target.account("wallet"), Some(account));
+    }
+
+    #[tokio::test]
+    async fn missing_requested_account_is_an_error() {
+        let source = FakeSurfnet::start().await;
+        let target = FakeSurfnet::start().await;
+        source.set_account("wallet", FakeAccount::new(SYSTEM_PROGRAM_ID, vec![]));
+
+        let result = clone_accounts(
+            &source.rpc(),
+            &target.rpc(),
+            &["wallet".to_string(), "missing".to_string()],
+            false,
+            None,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(target.account("wallet"), None);
+    }
+}
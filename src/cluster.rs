@@ -0,0 +1,111 @@
+use std::fmt;
+
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::rpc::Rpc;
+
+const MAINNET_GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
+const DEVNET_GENESIS_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
+const TESTNET_GENESIS_HASH: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
+/// Cheatcode used to detect surfpool. Called without params, so it never writes anything and surfpool answers with
+/// "invalid params". Any other answer is taken as not surfpool, so a provider with its own error codes is not let through
+const SURFNET_PROBE_METHOD: &str = "surfnet_setAccount";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterKind {
+    Surfpool,
+    /// Anything that is not surfpool and not a known cluster, most likely `solana-test-validator`
+    TestValidator,
+    /// Holds the name of the cluster
+    RealCluster(&'static str),
+}
+
+impl fmt::Display for ClusterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClusterKind::Surfpool => write!(f, "surfpool"),
+            ClusterKind::TestValidator => write!(f, "test validator"),
+            ClusterKind::RealCluster(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl ClusterKind {
+    pub fn is_local_fork(&self) -> bool {
+        matches!(self, ClusterKind::Surfpool)
+    }
+}
+
+/// Surfpool forks mainnet and may report its genesis hash, so the cheatcode check has to come first
+pub async fn classify_cluster(rpc: &Rpc) -> Result<ClusterKind> {
+    if rpc.rejects_empty_params(SURFNET_PROBE_METHOD).await? {
+        return Ok(ClusterKind::Surfpool);
+    }
+
+    let kind = match rpc.get_genesis_hash().await?.as_str() {
+        MAINNET_GENESIS_HASH => ClusterKind::RealCluster("mainnet-beta"),
+        DEVNET_GENESIS_HASH => ClusterKind::RealCluster("devnet"),
+        TESTNET_GENESIS_HASH => ClusterKind::RealCluster("testnet"),
+        _ => ClusterKind::TestValidator,
+    };
+
+    Ok(kind)
+}
+
+/// Fails unless the RPC is a local fork. `allow_any_cluster` turns the failure into a warning
+pub async fn ensure_local_fork(rpc: &Rpc, allow_any_cluster: bool) -> Result<()> {
+    let kind = classify_cluster(rpc).await?;
+    info!("RPC {} looks like {}", rpc.url, kind);
+
+    if kind.is_local_fork() {
+        return Ok(());
+    }
+
+    if allow_any_cluster {
+        warn!(
+            "{} is {}, not a local fork, running anyway because of --allow-any-cluster",
+            rpc.url, kind
+        );
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "Refusing to write to {}: it is {}, not a surfpool fork. Use --allow-any-cluster to override",
+        rpc.url,
+        kind
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeSurfnet;
+
+    #[tokio::test]
+    async fn surfpool_is_a_local_fork() {
+        let surfnet = FakeSurfnet::start().await;
+        assert_eq!(
+            classify_cluster(&surfnet.rpc()).await.unwrap(),
+            ClusterKind::Surfpool
+        );
+        ensure_local_fork(&surfnet.rpc(), false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn other_probe_errors_are_refused() {
+        // internal error, invalid request, method not found
+        for code in [-32603, -32600, -32601] {
+            let surfnet = FakeSurfnet::start().await;
+            surfnet.answer_probe_with(code);
+
+            assert_eq!(
+                classify_cluster(&surfnet.rpc()).await.unwrap(),
+                ClusterKind::TestValidator
+            );
+            assert!(ensure_local_fork(&surfnet.rpc(), false).await.is_err());
+            ensure_local_fork(&surfnet.rpc(), true).await.unwrap();
+        }
+    }
+}
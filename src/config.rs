@@ -0,0 +1,240 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::rpc::RetryPolicy;
+
+pub const DEFAULT_RPC_URL: &str = "http://localhost:8899";
+pub const DEFAULT_CONFIG_PATH: &str = "nft_robber.toml";
+
+/// Contents of the config file, something like:
+/// ```toml
+/// default_profile = "local"
+///
+/// [profiles.local]
+/// rpc_url = "http://localhost:8899"
+///
+/// [profiles.staging-fork]
+/// rpc_url = "https://fork.example.com"
+/// auth_token = "secret"
+/// headers = { "X-Team" = "nft" }
+/// timeout_secs = 60
+/// max_retries = 5
+/// ```
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Profile {
+    pub rpc_url: Option<String>,
+    /// Sent as `Authorization: Bearer <auth_token>`
+    pub auth_token: Option<String>,
+    /// Extra headers sent on every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub timeout_secs: Option<u64>,
+    pub max_retries: Option<u32>,
+}
+
+/// Everything needed to build the `Rpc` client
+#[derive(Debug)]
+pub struct RpcSettings {
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub retry_policy: RetryPolicy,
+}
+
+/// Values given on the command line, these take priority over the profile
+#[derive(Debug, Default)]
+pub struct Overrides {
+    pub rpc_url: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub max_retries: Option<u32>,
+}
+
+impl Config {
+    /// A missing file is only an error if the user explicitly asked for it
+    pub fn load(path: &Path, required: bool) -> Result<Self> {
+        if !path.exists() {
+            if required {
+                anyhow::bail!("Config file {} does not exist", path.display());
+            }
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read config file {}", path.display()))?;
+        let config: Config = toml::from_str(&text)
+            .with_context(|| format!("Could not parse config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Priority is command line (or `RPC_URL` for the url) > profile > default.
+    /// Headers always come from the profile
+    pub fn resolve(&self, profile: Option<&str>, overrides: Overrides) -> Result<RpcSettings> {
+        let profile = match profile.or(self.default_profile.as_deref()) {
+            Some(name) => {
+                info!("Using profile {}", name);
+                self.profiles
+                    .get(name)
+                    .cloned()
+                    .with_context(|| format!("Profile {} not found in config", name))?
+            }
+            None => Profile::default(),
+        };
+
+        let mut headers = profile.headers;
+        if let Some(token) = profile.auth_token {
+            if headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case("authorization"))
+            {
+                anyhow::bail!("The profile has both auth_token and an Authorization header");
+            }
+            headers.insert("Authorization".into(), format!("Bearer {}", token));
+        }
+
+        // header names are case insensitive, which spelling would get sent is random
+        let mut names = HashSet::new();
+        for name in headers.keys() {
+            if !names.insert(name.to_ascii_lowercase()) {
+                anyhow::bail!("The profile sets header {} more than once", name);
+            }
+        }
+
+        let url = overrides
+            .rpc_url
+            .or(profile.rpc_url)
+            .unwrap_or_else(|| DEFAULT_RPC_URL.into());
+
+        let mut retry_policy = RetryPolicy::default();
+        if let Some(timeout_secs) = overrides.timeout_secs.or(profile.timeout_secs) {
+            retry_policy.timeout = Duration::from_secs(timeout_secs);
+        }
+        if let Some(max_retries) = overrides.max_retries.or(profile.max_retries) {
+            retry_policy.max_retries = max_retries;
+        }
+
+        Ok(RpcSettings {
+            url,
+            headers,
+            retry_policy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        default_profile = "local"
+
+        [profiles.local]
+        rpc_url = "http://localhost:9999"
+        max_retries = 7
+
+        [profiles.remote]
+        rpc_url = "https://fork.example.com"
+        auth_token = "secret"
+        headers = { "X-Team" = "nft" }
+        timeout_secs = 60
+    "#;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn command_line_beats_profile_beats_default() {
+        let config = config(CONFIG);
+
+        let settings = config
+            .resolve(Some("remote"), Overrides::default())
+            .unwrap();
+        assert_eq!(settings.url, "https://fork.example.com");
+        assert_eq!(settings.retry_policy.timeout, Duration::from_secs(60));
+        assert_eq!(
+            settings.retry_policy.max_retries,
+            RetryPolicy::default().max_retries
+        );
+
+        let overrides = Overrides {
+            rpc_url: Some("http://cli:8899".into()),
+            timeout_secs: Some(5),
+            max_retries: Some(1),
+        };
+        let settings = config.resolve(Some("remote"), overrides).unwrap();
+        assert_eq!(settings.url, "http://cli:8899");
+        assert_eq!(settings.retry_policy.timeout, Duration::from_secs(5));
+        assert_eq!(settings.retry_policy.max_retries, 1);
+
+        let settings = Config::default()
+            .resolve(None, Overrides::default())
+            .unwrap();
+        assert_eq!(settings.url, DEFAULT_RPC_URL);
+        assert!(settings.headers.is_empty());
+    }
+
+    #[test]
+    fn default_profile_is_used_unless_another_is_picked() {
+        let config = config(CONFIG);
+
+        let settings = config.resolve(None, Overrides::default()).unwrap();
+        assert_eq!(settings.url, "http://localhost:9999");
+        assert_eq!(settings.retry_policy.max_retries, 7);
+
+        let settings = config
+            .resolve(Some("remote"), Overrides::default())
+            .unwrap();
+        assert_eq!(settings.url, "https://fork.example.com");
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        assert!(
+            config(CONFIG)
+                .resolve(Some("missing"), Overrides::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn auth_token_becomes_a_header() {
+        let settings = config(CONFIG)
+            .resolve(Some("remote"), Overrides::default())
+            .unwrap();
+        assert_eq!(settings.headers["Authorization"], "Bearer secret");
+        assert_eq!(settings.headers["X-Team"], "nft");
+    }
+
+    #[test]
+    fn conflicting_headers_are_rejected() {
+        let both = config(
+            r#"
+            [profiles.p]
+            auth_token = "secret"
+            headers = { "authorization" = "Basic other" }
+            "#,
+        );
+        assert!(both.resolve(Some("p"), Overrides::default()).is_err());
+
+        let twice = config(
+            r#"
+            [profiles.p]
+            headers = { "X-Team" = "a", "x-team" = "b" }
+            "#,
+        );
+        assert!(twice.resolve(Some("p"), Overrides::default()).is_err());
+    }
+}
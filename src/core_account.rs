@@ -0,0 +1,676 @@
+//! Full (de)serialization of mpl-core assets and collections, plugins included.
+//!
+//! Layout of the account:
+//! header (`BaseAssetV1` / `BaseCollectionV1`), `PluginHeaderV1`, plugin data, external adapter data, `PluginRegistryV1`.
+//! The registry has the offset of every plugin and the plugin header has the offset of the registry,
+//! so everything after anything that changes size has to be moved. `to_bytes` just lays everything out again.
+//! The registry is read by hand so a plugin type this version of mpl-core doesn't know is kept as raw bytes
+//! instead of failing the whole account
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use borsh::{BorshDeserialize, BorshSerialize};
+use log::warn;
+use mpl_core::{
+    accounts::{BaseAssetV1, BaseCollectionV1, PluginHeaderV1},
+    types::{BasePluginAuthority, ExternalRegistryRecord, Key, Plugin, PluginType},
+};
+use solana_pubkey::Pubkey;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginEntry {
+    pub authority: BasePluginAuthority,
+    pub plugin: Plugin,
+}
+
+/// A plugin whose type this version of mpl-core doesn't know, its bytes are only moved around
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownPluginEntry {
+    /// The `PluginType` byte in the registry
+    pub plugin_type: u8,
+    pub authority: BasePluginAuthority,
+    /// The serialized plugin
+    pub plugin: Vec<u8>,
+}
+
+/// External plugin adapters are not decoded, their bytes are only moved around
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalPluginEntry {
+    /// The offsets in here are recomputed when serializing
+    pub record: ExternalRegistryRecord,
+    /// The serialized `ExternalPluginAdapter`
+    pub adapter: Vec<u8>,
+    /// The data section of adapters that have one (app data etc)
+    pub data: Option<Vec<u8>>,
+}
+
+/// Everything in an mpl-core asset or collection account
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreAccount<H> {
+    pub header: H,
+    /// Whether there is a plugin header and registry, they can be there with no plugins in them
+    pub has_registry: bool,
+    pub plugins: Vec<PluginEntry>,
+    /// Written back after `plugins`
+    pub unknown_plugins: Vec<UnknownPluginEntry>,
+    pub external_plugins: Vec<ExternalPluginEntry>,
+}
+
+pub type CoreAsset = CoreAccount<BaseAssetV1>;
+pub type CoreCollection = CoreAccount<BaseCollectionV1>;
+
+/// `BaseAssetV1` or `BaseCollectionV1`, with the key their accounts start with
+pub trait CoreHeader: BorshSerialize + BorshDeserialize {
+    const KEY: Key;
+}
+
+impl CoreHeader for BaseAssetV1 {
+    const KEY: Key = Key::AssetV1;
+}
+
+impl CoreHeader for BaseCollectionV1 {
+    const KEY: Key = Key::CollectionV1;
+}
+
+/// Same layout as `RegistryRecord`, with the plugin type left as a byte
+#[derive(BorshSerialize, BorshDeserialize)]
+struct RawRegistryRecord {
+    plugin_type: u8,
+    authority: BasePluginAuthority,
+    offset: u64,
+}
+
+/// Same layout as `PluginRegistryV1`
+#[derive(BorshSerialize, BorshDeserialize)]
+struct RawPluginRegistry {
+    key: Key,
+    registry: Vec<RawRegistryRecord>,
+    external_registry: Vec<ExternalRegistryRecord>,
+}
+
+fn bytes_at(bytes: &[u8], offset: u64) -> Result<&[u8]> {
+    bytes
+        .get(offset as usize..)
+        .ok_or_else(|| anyhow!("Offset {} is past the end of the account", offset))
+}
+
+impl<H: CoreHeader> CoreAccount<H> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let key = bytes
+            .first()
+            .and_then(|&key| Key::try_from_slice(&[key]).ok());
+        if key != Some(H::KEY) {
+            anyhow::bail!("Expected a {:?} account, found {:?}", H::KEY, key);
+        }
+
+        let mut rest = bytes;
+        let header = H::deserialize(&mut rest)?;
+        let header_len = bytes.len() - rest.len();
+
+        if rest.is_empty() {
+            return Ok(Self {
+                header,
+                has_registry: false,
+                plugins: Vec::new(),
+                unknown_plugins: Vec::new(),
+                external_plugins: Vec::new(),
+            });
+        }
+
+        let plugin_header = PluginHeaderV1::deserialize(&mut rest)?;
+        if plugin_header.key != Key::PluginHeaderV1 {
+            anyhow::bail!(
+                "Expected a plugin header after the {} byte header, found {:?}",
+                header_len,
+                plugin_header.key
+            );
+        }
+
+        let registry_offset = plugin_header.plugin_registry_offset;
+        let registry = RawPluginRegistry::deserialize(&mut bytes_at(bytes, registry_offset)?)?;
+        if registry.key != Key::PluginRegistryV1 {
+            anyhow::bail!(
+                "Expected a plugin registry at {}, found {:?}",
+                registry_offset,
+                registry.key
+            );
+        }
+
+        // every section starts where the previous one ends, which is the only way to know how long
+        // the adapters and unknown plugins are
+        let mut section_starts: Vec<u64> = registry
+            .external_registry
+            .iter()
+            .flat_map(|record| [Some(record.offset), record.data_offset])
+            .flatten()
+            .chain(registry.registry.iter().map(|record| record.offset))
+            .chain([registry_offset])
+            .collect();
+        section_starts.sort_unstable();
+        let section = |start: u64, what: &str| -> Result<Vec<u8>> {
+            let end = section_starts
+                .iter()
+                .copied()
+                .find(|&section_start| section_start > start)
+                .unwrap_or(registry_offset);
+            Ok(bytes
+                .get(start as usize..end as usize)
+                .ok_or_else(|| anyhow!("{} at {} is out of bounds", what, start))?
+                .to_vec())
+        };
+
+        let mut plugins = Vec::new();
+        let mut unknown_plugins = Vec::new();
+        for record in registry.registry {
+            if PluginType::try_from_slice(&[record.plugin_type]).is_ok() {
+                plugins.push(PluginEntry {
+                    authority: record.authority,
+                    plugin: Plugin::deserialize(&mut bytes_at(bytes, record.offset)?)?,
+                });
+            } else {
+                warn!(
+                    "Unknown plugin type {} at {}, keeping it as it is",
+                    record.plugin_type, record.offset
+                );
+                unknown_plugins.push(UnknownPluginEntry {
+                    plugin_type: record.plugin_type,
+                    authority: record.authority,
+                    plugin: section(record.offset, "Plugin")?,
+                });
+            }
+        }
+
+        let mut external_plugins = Vec::new();
+        for record in registry.external_registry {
+            let adapter = section(record.offset, "External plugin")?;
+
+            let data = match (record.data_offset, record.data_len) {
+                (Some(offset), Some(len)) => {
+                    let out_of_bounds =
+                        || anyhow!("External plugin data at {} is out of bounds", offset);
+                    let end = offset.checked_add(len).ok_or_else(out_of_bounds)?;
+                    Some(
+                        bytes
+                            .get(offset as usize..end as usize)
+                            .ok_or_else(out_of_bounds)?
+                            .to_vec(),
+                    )
+                }
+                _ => None,
+            };
+
+            external_plugins.push(ExternalPluginEntry {
+                record,
+                adapter,
+                data,
+            });
+        }
+
+        Ok(Self {
+            header,
+            has_registry: true,
+            plugins,
+            unknown_plugins,
+            external_plugins,
+        })
+    }
+
+    /// Lays out the header, plugins, external adapters and registry one after the other, with new offsets
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = borsh::to_vec(&self.header)?;
+
+        if !self.has_registry
+            && self.plugins.is_empty()
+            && self.unknown_plugins.is_empty()
+            && self.external_plugins.is_empty()
+        {
+            return Ok(bytes);
+        }
+
+        // the registry offset is only known at the end, this gets overwritten
+        let plugin_header_offset = bytes.len();
+        bytes.extend(borsh::to_vec(&PluginHeaderV1 {
+            key: Key::PluginHeaderV1,
+            plugin_registry_offset: 0,
+        })?);
+
+        let mut registry = Vec::new();
+        for entry in &self.plugins {
+            registry.push(RawRegistryRecord {
+                plugin_type: borsh::to_vec(&PluginType::from(&entry.plugin))?[0],
+                authority: entry.authority.clone(),
+                offset: bytes.len() as u64,
+            });
+            bytes.extend(borsh::to_vec(&entry.plugin)?);
+        }
+        for entry in &self.unknown_plugins {
+            registry.push(RawRegistryRecord {
+                plugin_type: entry.plugin_type,
+                authority: entry.authority.clone(),
+                offset: bytes.len() as u64,
+            });
+            bytes.extend_from_slice(&entry.plugin);
+        }
+
+        let mut external_registry = Vec::new();
+        for entry in &self.external_plugins {
+            let mut record = entry.record.clone();
+            record.offset = bytes.len() as u64;
+            bytes.extend_from_slice(&entry.adapter);
+
+            match &entry.data {
+                Some(data) => {
+                    record.data_offset = Some(bytes.len() as u64);
+                    record.data_len = Some(data.len() as u64);
+                    bytes.extend_from_slice(data);
+                }
+                None => {
+                    record.data_offset = None;
+                    record.data_len = None;
+                }
+            }
+            external_registry.push(record);
+        }
+
+        let plugin_header = borsh::to_vec(&PluginHeaderV1 {
+            key: Key::PluginHeaderV1,
+            plugin_registry_offset: bytes.len() as u64,
+        })?;
+        bytes[plugin_header_offset..plugin_header_offset + plugin_header.len()]
+            .copy_from_slice(&plugin_header);
+
+        bytes.extend(borsh::to_vec(&RawPluginRegistry {
+            key: Key::PluginRegistryV1,
+            registry,
+            external_registry,
+        })?);
+
+        Ok(bytes)
+    }
+
+    /// Removes the plugin of this type, the rest stay as they are. Returns whether there was one
+    pub fn remove_plugin(&mut self, plugin_type: PluginType) -> bool {
+        let before = self.plugins.len();
+        self.plugins
+            .retain(|entry| PluginType::from(&entry.plugin) != plugin_type);
+        self.plugins.len() != before
+    }
+
+    /// Sets `frozen` to false on the `FreezeDelegate` and `PermanentFreezeDelegate` plugins.
+    /// With `revoke`, their authority goes back to who manages them (the owner and the update authority),
+    /// like mpl-core's revoke does. Returns whether there was any freeze plugin
+    pub fn unfreeze(&mut self, revoke: bool) -> bool {
+        let mut found = false;
+        for entry in &mut self.plugins {
+            let manager = match &mut entry.plugin {
+                Plugin::FreezeDelegate(freeze) => {
+                    freeze.frozen = false;
+                    BasePluginAuthority::Owner
+                }
+                Plugin::PermanentFreezeDelegate(freeze) => {
+                    freeze.frozen = false;
+                    BasePluginAuthority::UpdateAuthority
+                }
+                _ => continue,
+            };
+
+            found = true;
+            if revoke {
+                entry.authority = manager;
+            }
+        }
+        found
+    }
+}
+
+/// Plugin type by name, ignoring case, `-` and `_` (`freeze-delegate`, `FreezeDelegate`)
+pub fn parse_plugin_type(name: &str) -> Result<PluginType> {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let wanted = normalize(name);
+
+    // every variant is a single borsh byte, so this lists all the types this version of mpl-core knows
+    let known: Vec<PluginType> = (0..=u8::MAX)
+        .map_while(|variant| PluginType::try_from_slice(&[variant]).ok())
+        .collect();
+
+    known
+        .iter()
+        .find(|plugin_type| normalize(&format!("{:?}", plugin_type)) == wanted)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown plugin type {}, expected one of {:?}", name, known))
+}
+
+/// Plugins the owner manages. A transfer gives them back to the new owner, which clears their delegates.
+/// Same list as the plugin types `PluginType::manager` in the mpl-core program gives `Authority::Owner`
+pub fn is_owner_managed(plugin_type: &PluginType) -> bool {
+    matches!(
+        plugin_type,
+        PluginType::TransferDelegate
+            | PluginType::FreezeDelegate
+            | PluginType::BurnDelegate
+            | PluginType::Autograph
+    )
+}
+
+/// `owner`, `update-authority`, `none` or a pubkey
+pub fn parse_plugin_authority(authority: &str) -> Result<BasePluginAuthority> {
+    Ok(match authority.to_lowercase().as_str() {
+        "owner" => BasePluginAuthority::Owner,
+        "update-authority" | "update_authority" => BasePluginAuthority::UpdateAuthority,
+        "none" => BasePluginAuthority::None,
+        _ => BasePluginAuthority::Address {
+            address: Pubkey::from_str(authority).map_err(|_| {
+                anyhow!(
+                    "{} is not owner, update-authority, none or a pubkey",
+                    authority
+                )
+            })?,
+        },
+    })
+}
+
+/// `<plugin type>=<authority>`, where the authority can also be `keep`, which gives `None`
+pub fn parse_authority_override(value: &str) -> Result<(PluginType, Option<BasePluginAuthority>)> {
+    let (plugin_type, authority) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected <plugin type>=<authority>, got {}", value))?;
+
+    let authority = if authority.eq_ignore_ascii_case("keep") {
+        None
+    } else {
+        Some(parse_plugin_authority(authority)?)
+    };
+    Ok((parse_plugin_type(plugin_type)?, authority))
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_core::types::{Attribute, Attributes, ExternalPluginAdapterType, FreezeDelegate};
+
+    use super::*;
+    use crate::test_support::core_asset;
+
+    fn asset() -> CoreAsset {
+        CoreAsset {
+            has_registry: true,
+            plugins: vec![
+                PluginEntry {
+                    authority: BasePluginAuthority::Owner,
+                    plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: true }),
+                },
+                PluginEntry {
+                    authority: BasePluginAuthority::UpdateAuthority,
+                    plugin: Plugin::Attributes(Attributes {
+                        attribute_list: vec![Attribute {
+                            key: "level".into(),
+                            value: "1".into(),
+                        }],
+                    }),
+                },
+            ],
+            ..core_asset(Pubkey::new_from_array([1; 32]))
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let asset = asset();
+        let bytes = asset.to_bytes().unwrap();
+        assert_eq!(CoreAsset::from_bytes(&bytes).unwrap(), asset);
+
+        // the offsets have to agree with what mpl-core reads
+        let records = mpl_core::fetch_plugins(&bytes).unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    /// The asset header, the plugin header, `sections` one after the other and the registry `registry` makes
+    /// from the offsets of the sections. Lets the tests lay out accounts in orders `to_bytes` doesn't use
+    fn laid_out_by_hand(
+        sections: &[Vec<u8>],
+        registry: impl FnOnce(&[u64]) -> RawPluginRegistry,
+    ) -> Vec<u8> {
+        let mut bytes = borsh::to_vec(&asset().header).unwrap();
+        let plugin_header_offset = bytes.len();
+        bytes.extend(
+            borsh::to_vec(&PluginHeaderV1 {
+                key: Key::PluginHeaderV1,
+                plugin_registry_offset: 0,
+            })
+            .unwrap(),
+        );
+
+        let mut offsets = Vec::new();
+        for section in sections {
+            offsets.push(bytes.len() as u64);
+            bytes.extend_from_slice(section);
+        }
+
+        // the registry offset comes right after the key of the plugin header
+        let registry_offset = (bytes.len() as u64).to_le_bytes();
+        bytes[plugin_header_offset + 1..plugin_header_offset + 9].copy_from_slice(&registry_offset);
+        bytes.extend(borsh::to_vec(&registry(&offsets)).unwrap());
+        bytes
+    }
+
+    fn raw_record(
+        plugin: &Plugin,
+        authority: BasePluginAuthority,
+        offset: u64,
+    ) -> RawRegistryRecord {
+        RawRegistryRecord {
+            plugin_type: borsh::to_vec(&PluginType::from(plugin)).unwrap()[0],
+            authority,
+            offset,
+        }
+    }
+
+    #[test]
+    fn external_adapter_between_plugins() {
+        let [freeze, attributes] = [0, 1].map(|i| asset().plugins[i].clone());
+        // the codec never decodes adapters, so any bytes do
+        let adapter = vec![9, 8, 7];
+        let data = b"some app data".to_vec();
+
+        let bytes = laid_out_by_hand(
+            &[
+                borsh::to_vec(&freeze.plugin).unwrap(),
+                adapter.clone(),
+                data.clone(),
+                borsh::to_vec(&attributes.plugin).unwrap(),
+            ],
+            |offsets| RawPluginRegistry {
+                key: Key::PluginRegistryV1,
+                registry: vec![
+                    raw_record(&freeze.plugin, freeze.authority.clone(), offsets[0]),
+                    raw_record(&attributes.plugin, attributes.authority.clone(), offsets[3]),
+                ],
+                external_registry: vec![ExternalRegistryRecord {
+                    plugin_type: ExternalPluginAdapterType::AppData,
+                    authority: BasePluginAuthority::UpdateAuthority,
+                    lifecycle_checks: None,
+                    offset: offsets[1],
+                    data_offset: Some(offsets[2]),
+                    data_len: Some(data.len() as u64),
+                }],
+            },
+        );
+
+        let parsed = CoreAsset::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.plugins, asset().plugins);
+        assert_eq!(parsed.external_plugins.len(), 1);
+        assert_eq!(parsed.external_plugins[0].adapter, adapter);
+        assert_eq!(parsed.external_plugins[0].data, Some(data.clone()));
+
+        // laid out again in the usual order, with the offsets in the record updated
+        let rewritten = parsed.to_bytes().unwrap();
+        assert_eq!(mpl_core::fetch_plugins(&rewritten).unwrap().len(), 2);
+        let reparsed = CoreAsset::from_bytes(&rewritten).unwrap();
+        assert_eq!(reparsed.plugins, parsed.plugins);
+        assert_eq!(reparsed.external_plugins[0].adapter, adapter);
+        assert_eq!(reparsed.external_plugins[0].data, Some(data));
+        assert_eq!(reparsed.to_bytes().unwrap(), rewritten);
+    }
+
+    #[test]
+    fn unknown_plugins_are_kept() {
+        let freeze = asset().plugins[0].clone();
+        let unknown = UnknownPluginEntry {
+            plugin_type: u8::MAX,
+            authority: BasePluginAuthority::Owner,
+            plugin: vec![1, 2, 3, 4],
+        };
+
+        let bytes = laid_out_by_hand(
+            &[
+                unknown.plugin.clone(),
+                borsh::to_vec(&freeze.plugin).unwrap(),
+            ],
+            |offsets| RawPluginRegistry {
+                key: Key::PluginRegistryV1,
+                registry: vec![
+                    RawRegistryRecord {
+                        plugin_type: unknown.plugin_type,
+                        authority: unknown.authority.clone(),
+                        offset: offsets[0],
+                    },
+                    raw_record(&freeze.plugin, freeze.authority.clone(), offsets[1]),
+                ],
+                external_registry: Vec::new(),
+            },
+        );
+
+        let mut parsed = CoreAsset::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.plugins, vec![freeze]);
+        assert_eq!(parsed.unknown_plugins, vec![unknown]);
+
+        parsed.header.owner = Pubkey::new_from_array([7; 32]);
+        assert_eq!(
+            CoreAsset::from_bytes(&parsed.to_bytes().unwrap()).unwrap(),
+            parsed
+        );
+    }
+
+    #[test]
+    fn checks_the_account_key() {
+        let bytes = asset().to_bytes().unwrap();
+        assert!(CoreCollection::from_bytes(&bytes).is_err());
+        assert!(CoreAsset::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn plugins_survive_a_longer_name() {
+        let mut asset = asset();
+        asset.header.name = "A much longer name than before".into();
+
+        let bytes = asset.to_bytes().unwrap();
+        let parsed = CoreAsset::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.header.name, "A much longer name than before");
+        assert_eq!(parsed.plugins, asset.plugins);
+    }
+
+    #[test]
+    fn removing_a_plugin_keeps_the_others() {
+        let mut asset = asset();
+        assert!(asset.remove_plugin(PluginType::FreezeDelegate));
+        assert!(!asset.remove_plugin(PluginType::FreezeDelegate));
+
+        let parsed = CoreAsset::from_bytes(&asset.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed.plugins.len(), 1);
+        assert_eq!(parsed.plugins, asset().plugins[1..]);
+    }
+
+    #[test]
+    fn unfreezes_in_place() {
+        let mut asset = asset();
+        asset.plugins[0].authority = BasePluginAuthority::Address {
+            address: Pubkey::new_from_array([2; 32]),
+        };
+
+        let mut kept = asset.clone();
+        assert!(kept.unfreeze(false));
+        assert_eq!(
+            kept.plugins[0].plugin,
+            Plugin::FreezeDelegate(FreezeDelegate { frozen: false })
+        );
+        assert_eq!(kept.plugins[0].authority, asset.plugins[0].authority);
+        assert_eq!(kept.plugins[1], asset.plugins[1]);
+
+        let mut revoked = asset.clone();
+        assert!(revoked.unfreeze(true));
+        assert_eq!(revoked.plugins[0].authority, BasePluginAuthority::Owner);
+
+        assert!(
+            !CoreAsset {
+                plugins: Vec::new(),
+                ..asset
+            }
+            .unfreeze(true)
+        );
+    }
+
+    #[test]
+    fn parses_plugin_types() {
+        assert_eq!(
+            parse_plugin_type("freeze-delegate").unwrap(),
+            PluginType::FreezeDelegate
+        );
+        assert_eq!(
+            parse_plugin_type("Attributes").unwrap(),
+            PluginType::Attributes
+        );
+        assert!(parse_plugin_type("Nonsense").is_err());
+    }
+
+    #[test]
+    fn parses_authority_overrides() {
+        assert_eq!(
+            parse_authority_override("burn-delegate=owner").unwrap(),
+            (PluginType::BurnDelegate, Some(BasePluginAuthority::Owner))
+        );
+        assert_eq!(
+            parse_authority_override("FreezeDelegate=keep").unwrap(),
+            (PluginType::FreezeDelegate, None)
+        );
+        let address = Pubkey::new_from_array([5; 32]);
+        assert_eq!(
+            parse_authority_override(&format!("transfer-delegate={}", address)).unwrap(),
+            (
+                PluginType::TransferDelegate,
+                Some(BasePluginAuthority::Address { address })
+            )
+        );
+        assert!(parse_authority_override("transfer-delegate").is_err());
+        assert!(parse_authority_override("transfer-delegate=someone").is_err());
+    }
+
+    #[test]
+    fn owner_managed_plugins() {
+        for plugin_type in [
+            PluginType::TransferDelegate,
+            PluginType::FreezeDelegate,
+            PluginType::BurnDelegate,
+            PluginType::Autograph,
+        ] {
+            assert!(is_owner_managed(&plugin_type), "{:?}", plugin_type);
+        }
+        assert!(!is_owner_managed(&PluginType::PermanentFreezeDelegate));
+        assert!(!is_owner_managed(&PluginType::Attributes));
+    }
+
+    #[test]
+    fn no_plugins_is_just_the_header() {
+        let mut asset = asset();
+        asset.has_registry = false;
+        asset.plugins.clear();
+
+        let bytes = asset.to_bytes().unwrap();
+        assert_eq!(bytes, borsh::to_vec(&asset.header).unwrap());
+        assert_eq!(CoreAsset::from_bytes(&bytes).unwrap(), asset);
+    }
+}
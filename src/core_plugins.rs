@@ -0,0 +1,520 @@
+use std::{collections::HashSet, path::Path, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use mpl_core::{
+    accounts::{BaseAssetV1, BaseCollectionV1},
+    types::{
+        Attribute, Attributes, BasePluginAuthority, Creator, Key, Plugin, PluginType, Royalties,
+        RuleSet,
+    },
+};
+use solana_pubkey::Pubkey;
+
+use crate::{
+    core_account::{CoreAccount, CoreHeader, PluginEntry, is_owner_managed},
+    rent::RentMode,
+    rob::rewrite_core_account,
+    store::AccountStore,
+};
+
+fn edit_plugins<H: CoreHeader>(
+    data: &[u8],
+    edit: impl FnOnce(&mut Vec<PluginEntry>) -> Result<()>,
+) -> Result<Vec<u8>> {
+    let mut account = CoreAccount::<H>::from_bytes(data)?;
+    edit(&mut account.plugins)?;
+    // `to_bytes` adds the plugin header and registry if there weren't any
+    account.to_bytes()
+}
+
+/// Lets `edit` change the plugins of the core asset or collection at `key`, whichever it is,
+/// and writes it back with the rest of the account as it was
+pub async fn edit_core_plugins<S: AccountStore>(
+    store: &S,
+    key: &str,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+    edit: impl FnOnce(&mut Vec<PluginEntry>) -> Result<()>,
+) -> Result<()> {
+    rewrite_core_account(store, key, rent, snapshot, |data| {
+        match Key::from_slice(data, 0)? {
+            Key::AssetV1 => edit_plugins::<BaseAssetV1>(data, edit),
+            Key::CollectionV1 => edit_plugins::<BaseCollectionV1>(data, edit),
+            other => anyhow::bail!("{} is a {:?}, not a core asset or collection", key, other),
+        }
+    })
+    .await
+}
+
+/// Whoever manages this kind of plugin, which is what mpl-core gives it by default
+pub fn default_authority(plugin_type: &PluginType) -> BasePluginAuthority {
+    if is_owner_managed(plugin_type) {
+        BasePluginAuthority::Owner
+    } else {
+        BasePluginAuthority::UpdateAuthority
+    }
+}
+
+/// A plugin in mpl-core's JSON form, e.g. `{"Attributes": {"attribute_list": [{"key": "level", "value": "1"}]}}`
+pub fn parse_plugin(json: &str) -> Result<Plugin> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Adds a plugin to a core asset or collection. There can only be one plugin of each type
+pub async fn add_core_plugin<S: AccountStore>(
+    store: &S,
+    key: &str,
+    plugin: Plugin,
+    authority: Option<BasePluginAuthority>,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let plugin_type = PluginType::from(&plugin);
+    let authority = authority.unwrap_or_else(|| default_authority(&plugin_type));
+
+    edit_core_plugins(store, key, rent, snapshot, |plugins| {
+        if plugins
+            .iter()
+            .any(|entry| PluginType::from(&entry.plugin) == plugin_type)
+        {
+            anyhow::bail!("{} already has a {:?} plugin", key, plugin_type);
+        }
+
+        info!("Adding {:?} with authority {:?}", plugin_type, authority);
+        plugins.push(PluginEntry { authority, plugin });
+        Ok(())
+    })
+    .await
+}
+
+/// `trait=value`, the value can have `=` in it
+pub fn parse_attribute(attribute: &str) -> Result<(String, String)> {
+    let (key, value) = attribute
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected trait=value, got {}", attribute))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Overwrites the attributes that are already there, appends the rest and removes `remove`
+pub fn edit_attributes(
+    attribute_list: &mut Vec<Attribute>,
+    set: &[(String, String)],
+    remove: &[String],
+) {
+    for (key, value) in set {
+        match attribute_list
+            .iter_mut()
+            .find(|attribute| attribute.key == *key)
+        {
+            Some(attribute) => attribute.value = value.clone(),
+            None => attribute_list.push(Attribute {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+        }
+    }
+
+    for key in remove {
+        if !attribute_list.iter().any(|attribute| attribute.key == *key) {
+            warn!("There is no {} attribute to remove", key);
+        }
+        attribute_list.retain(|attribute| attribute.key != *key);
+    }
+}
+
+/// Edits the `Attributes` plugin of a core asset or collection, adding it if there is none and there is something to set
+pub async fn set_core_attributes<S: AccountStore>(
+    store: &S,
+    key: &str,
+    set: &[(String, String)],
+    remove: &[String],
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    edit_core_plugins(store, key, rent, snapshot, |plugins| {
+        let attributes = plugins
+            .iter_mut()
+            .find_map(|entry| match &mut entry.plugin {
+                Plugin::Attributes(attributes) => Some(attributes),
+                _ => None,
+            });
+
+        match attributes {
+            Some(attributes) => edit_attributes(&mut attributes.attribute_list, set, remove),
+            None if set.is_empty() => {
+                anyhow::bail!("{} has no Attributes plugin to remove traits from", key);
+            }
+            None => {
+                info!("Adding an Attributes plugin");
+                let mut attribute_list = Vec::new();
+                edit_attributes(&mut attribute_list, set, remove);
+                plugins.push(PluginEntry {
+                    authority: default_authority(&PluginType::Attributes),
+                    plugin: Plugin::Attributes(Attributes { attribute_list }),
+                });
+            }
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// `<pubkey>=<share>`, the share in percent
+pub fn parse_creator(creator: &str) -> Result<Creator> {
+    let (address, percentage) = creator
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected <pubkey>=<share>, got {}", creator))?;
+    Ok(Creator {
+        address: Pubkey::from_str(address)?,
+        percentage: percentage
+            .parse()
+            .map_err(|_| anyhow!("{} is not a share between 0 and 100", percentage))?,
+    })
+}
+
+/// What to change in a `Royalties` plugin, `None` keeps what is there
+#[derive(Debug, Clone, Default)]
+pub struct RoyaltiesChanges {
+    pub basis_points: Option<u16>,
+    pub creators: Option<Vec<Creator>>,
+    pub rule_set: Option<RuleSet>,
+}
+
+impl RoyaltiesChanges {
+    /// Fails the same way mpl-core would, so the plugin stays valid
+    pub fn apply(&self, royalties: &mut Royalties) -> Result<()> {
+        if let Some(basis_points) = self.basis_points {
+            royalties.basis_points = basis_points;
+        }
+        if let Some(creators) = &self.creators {
+            royalties.creators = creators.clone();
+        }
+        if let Some(rule_set) = &self.rule_set {
+            royalties.rule_set = rule_set.clone();
+        }
+
+        if royalties.basis_points > 10_000 {
+            anyhow::bail!("{} basis points is more than 100%", royalties.basis_points);
+        }
+        let mut addresses = HashSet::new();
+        for creator in &royalties.creators {
+            if !addresses.insert(creator.address) {
+                anyhow::bail!("{} is a creator more than once", creator.address);
+            }
+        }
+        let total: u32 = royalties
+            .creators
+            .iter()
+            .map(|creator| creator.percentage as u32)
+            .sum();
+        if total != 100 {
+            anyhow::bail!("The creator shares add up to {}, not 100", total);
+        }
+
+        Ok(())
+    }
+}
+
+/// Edits the `Royalties` plugin of a core asset or collection. If there is none, it is added,
+/// in which case the basis points and creators have to be given
+pub async fn set_core_royalties<S: AccountStore>(
+    store: &S,
+    key: &str,
+    changes: &RoyaltiesChanges,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    edit_core_plugins(store, key, rent, snapshot, |plugins| {
+        let royalties = plugins.iter_mut().find_map(|entry| match &mut entry.plugin {
+            Plugin::Royalties(royalties) => Some(royalties),
+            _ => None,
+        });
+
+        match royalties {
+            Some(royalties) => changes.apply(royalties)?,
+            None => {
+                if changes.basis_points.is_none() || changes.creators.is_none() {
+                    anyhow::bail!(
+                        "{} has no Royalties plugin, the basis points and creators are needed to add one",
+                        key
+                    );
+                }
+
+                info!("Adding a Royalties plugin");
+                let mut royalties = Royalties {
+                    basis_points: 0,
+                    creators: Vec::new(),
+                    rule_set: RuleSet::None,
+                };
+                changes.apply(&mut royalties)?;
+                plugins.push(PluginEntry {
+                    authority: default_authority(&PluginType::Royalties),
+                    plugin: Plugin::Royalties(royalties),
+                });
+            }
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_core::types::FreezeDelegate;
+
+    use super::*;
+    use crate::{
+        core_account::CoreAsset,
+        rent::minimum_balance,
+        test_support::{FakeAccount, FakeSurfnet, core_asset},
+    };
+
+    fn bare_asset() -> CoreAsset {
+        core_asset(Pubkey::new_from_array([2; 32]))
+    }
+
+    #[test]
+    fn parses_plugin_json() {
+        assert_eq!(
+            parse_plugin(r#"{"Attributes": {"attribute_list": [{"key": "level", "value": "1"}]}}"#)
+                .unwrap(),
+            Plugin::Attributes(Attributes {
+                attribute_list: vec![Attribute {
+                    key: "level".into(),
+                    value: "1".into(),
+                }],
+            })
+        );
+        assert!(parse_plugin(r#"{"NotAPlugin": {}}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn adds_a_registry_to_a_bare_asset() {
+        let surfnet = FakeSurfnet::start().await;
+        let asset = Pubkey::new_from_array([1; 32]);
+        let original = bare_asset().to_bytes().unwrap();
+        surfnet.set_account(
+            asset,
+            FakeAccount {
+                lamports: minimum_balance(original.len()),
+                ..FakeAccount::new(mpl_core::ID, original)
+            },
+        );
+
+        let attributes = Plugin::Attributes(Attributes {
+            attribute_list: Vec::new(),
+        });
+        add_core_plugin(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            attributes.clone(),
+            None,
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let account = surfnet.account(asset).unwrap();
+        assert_eq!(account.lamports, minimum_balance(account.data.len()));
+        assert_eq!(mpl_core::fetch_plugins(&account.data).unwrap().len(), 1);
+        assert_eq!(
+            CoreAsset::from_bytes(&account.data).unwrap().plugins,
+            vec![PluginEntry {
+                authority: BasePluginAuthority::UpdateAuthority,
+                plugin: attributes.clone(),
+            }]
+        );
+
+        // only one of each
+        assert!(
+            add_core_plugin(
+                &surfnet.rpc(),
+                &asset.to_string(),
+                attributes,
+                None,
+                RentMode::Adjust,
+                None,
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    fn attribute(key: &str, value: &str) -> Attribute {
+        Attribute {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn edits_attributes() {
+        let mut attribute_list = vec![attribute("level", "1"), attribute("class", "mage")];
+        edit_attributes(
+            &mut attribute_list,
+            &[
+                parse_attribute("level=99").unwrap(),
+                parse_attribute("formula=a=b").unwrap(),
+            ],
+            &["class".to_string()],
+        );
+        assert_eq!(
+            attribute_list,
+            vec![attribute("level", "99"), attribute("formula", "a=b")]
+        );
+        assert!(parse_attribute("level").is_err());
+    }
+
+    #[tokio::test]
+    async fn removing_without_attributes_changes_nothing() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let surfnet = FakeSurfnet::with_core_account(asset, &bare_asset()).await;
+        let original = surfnet.account(asset);
+
+        assert!(
+            set_core_attributes(
+                &surfnet.rpc(),
+                &asset.to_string(),
+                &[],
+                &["level".to_string()],
+                RentMode::Adjust,
+                None,
+            )
+            .await
+            .is_err()
+        );
+        assert_eq!(surfnet.account(asset), original);
+    }
+
+    #[tokio::test]
+    async fn attributes_grow_and_keep_later_plugins() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let mut original = bare_asset();
+        original.plugins = vec![
+            PluginEntry {
+                authority: BasePluginAuthority::UpdateAuthority,
+                plugin: Plugin::Attributes(Attributes {
+                    attribute_list: vec![attribute("level", "1")],
+                }),
+            },
+            PluginEntry {
+                authority: BasePluginAuthority::Owner,
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: true }),
+            },
+        ];
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        set_core_attributes(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            &[("background".to_string(), "a very long value".to_string())],
+            &[],
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let edited = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        assert_eq!(
+            edited.plugins[0].plugin,
+            Plugin::Attributes(Attributes {
+                attribute_list: vec![
+                    attribute("level", "1"),
+                    attribute("background", "a very long value")
+                ],
+            })
+        );
+        assert_eq!(edited.plugins[1], original.plugins[1]);
+    }
+
+    #[tokio::test]
+    async fn edits_royalties() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let surfnet = FakeSurfnet::with_core_account(asset, &bare_asset()).await;
+        let first = Pubkey::new_from_array([5; 32]);
+        let second = Pubkey::new_from_array([6; 32]);
+
+        // there is nothing to keep, so everything but the rule set is needed
+        let only_basis_points = RoyaltiesChanges {
+            basis_points: Some(500),
+            ..Default::default()
+        };
+        let set = |changes: RoyaltiesChanges| {
+            let rpc = surfnet.rpc();
+            async move {
+                set_core_royalties(&rpc, &asset.to_string(), &changes, RentMode::Adjust, None).await
+            }
+        };
+        assert!(set(only_basis_points.clone()).await.is_err());
+
+        set(RoyaltiesChanges {
+            creators: Some(vec![
+                parse_creator(&format!("{}=70", first)).unwrap(),
+                parse_creator(&format!("{}=30", second)).unwrap(),
+            ]),
+            ..only_basis_points
+        })
+        .await
+        .unwrap();
+
+        set(RoyaltiesChanges {
+            rule_set: Some(RuleSet::ProgramDenyList(vec![second])),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let edited = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        assert_eq!(
+            edited.plugins,
+            vec![PluginEntry {
+                authority: BasePluginAuthority::UpdateAuthority,
+                plugin: Plugin::Royalties(Royalties {
+                    basis_points: 500,
+                    creators: vec![
+                        Creator {
+                            address: first,
+                            percentage: 70
+                        },
+                        Creator {
+                            address: second,
+                            percentage: 30
+                        },
+                    ],
+                    rule_set: RuleSet::ProgramDenyList(vec![second]),
+                }),
+            }]
+        );
+
+        // shares have to add up to 100
+        assert!(
+            set(RoyaltiesChanges {
+                creators: Some(vec![Creator {
+                    address: first,
+                    percentage: 50
+                }]),
+                ..Default::default()
+            })
+            .await
+            .is_err()
+        );
+
+        // and each creator can only be there once
+        assert!(
+            set(RoyaltiesChanges {
+                creators: Some(vec![
+                    parse_creator(&format!("{}=50", first)).unwrap(),
+                    parse_creator(&format!("{}=50", first)).unwrap(),
+                ]),
+                ..Default::default()
+            })
+            .await
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::{
+    store::{AccountFile, AccountStore},
+    utils::check_key_valid,
+    write_set::WriteSet,
+};
+
+/// Writes every account to `<out>/<pubkey>.json`, in the layout `solana-test-validator --account` and
+/// Anchor's `[[test.validator.account]]` read
+pub async fn dump_accounts<S: AccountStore>(
+    store: &S,
+    pubkeys: &[String],
+    out: &Path,
+) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out)?;
+
+    let mut paths = Vec::new();
+    for (pubkey, account) in pubkeys.iter().zip(store.get_many(pubkeys).await?) {
+        let account = account.ok_or_else(|| anyhow!("{} does not exist", pubkey))?;
+
+        let path = out.join(format!("{}.json", pubkey));
+        AccountFile::new(pubkey, &account).save(&path)?;
+        println!("Wrote {}", path.display());
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Writes the accounts in the given account files, the opposite of `dump_accounts`
+pub async fn load_accounts<S: AccountStore>(
+    store: &S,
+    files: &[PathBuf],
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let mut write_set = WriteSet::new();
+    for path in files {
+        let file = AccountFile::load(path)?;
+        // the pubkey ends up in a file name with --accounts-dir
+        check_key_valid(&file.pubkey).with_context(|| format!("In {}", path.display()))?;
+        let account = file.decode()?;
+        println!(
+            "Loading {} ({} bytes, owned by {})",
+            file.pubkey,
+            account.data.len(),
+            account.owner
+        );
+        write_set.set(&file.pubkey, account.to_set_account_info()?);
+    }
+
+    write_set.apply(store, snapshot).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        rpc::{SYSTEM_PROGRAM_ID, SetAccountInfo},
+        store::MemoryStore,
+        test_support::TempDir,
+    };
+
+    /// Dumps one account to `dir` and returns its pubkey and file
+    async fn dump_one(dir: &TempDir) -> (String, PathBuf) {
+        let wallet = Pubkey::new_from_array([1; 32]).to_string();
+        let source = MemoryStore::new();
+        source
+            .set(
+                &wallet,
+                &SetAccountInfo {
+                    data: Some(hex::encode([1, 2, 3])),
+                    executable: false,
+                    lamports: 42,
+                    owner: SYSTEM_PROGRAM_ID.into(),
+                    rent_epoch: u64::MAX,
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut paths = dump_accounts(&source, std::slice::from_ref(&wallet), dir.path())
+            .await
+            .unwrap();
+        (wallet, paths.remove(0))
+    }
+
+    #[tokio::test]
+    async fn dumped_accounts_load_back() {
+        let dir = TempDir::new("fixtures");
+        let (wallet, path) = dump_one(&dir).await;
+
+        let target = MemoryStore::new();
+        load_accounts(&target, &[path], None).await.unwrap();
+
+        let loaded = target.get(&wallet).await.unwrap().unwrap();
+        assert_eq!(loaded.data, vec![1, 2, 3]);
+        assert_eq!(loaded.lamports, 42);
+        assert_eq!(loaded.rent_epoch, u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn bad_pubkeys_are_rejected() {
+        let dir = TempDir::new("fixtures_bad_pubkey");
+        let (wallet, path) = dump_one(&dir).await;
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replace(&wallet, "../wallet")).unwrap();
+
+        let target = MemoryStore::new();
+        assert!(load_accounts(&target, &[path], None).await.is_err());
+        assert!(target.get("../wallet").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,17 @@
+//! The account level parts of the robber, so other test harnesses (LiteSVM etc) can rob accounts in their own
+//! `AccountStore` without going through the CLI or an RPC
+
+pub mod core_account;
+pub mod core_plugins;
+pub mod mpl;
+pub mod pnft;
+pub mod rent;
+pub mod rob;
+pub mod rpc;
+pub mod snapshot;
+pub mod store;
+pub mod surfnet;
+#[cfg(test)]
+mod test_support;
+pub mod utils;
+pub mod write_set;
@@ -1,38 +1,92 @@
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::{
+    balance::*,
+    clone::*,
+    cluster::*,
+    config::*,
+    core_account::{parse_authority_override, parse_plugin_authority, parse_plugin_type},
+    core_plugins::*,
+    fixtures::*,
     mpl::*,
-    pnft::{
-        deser_ata, deser_token_record, print_ata, print_metadata, print_token_record, ser_ata,
-        ser_token_record,
-    },
+    pnft::{print_ata, print_metadata, print_token_record},
     print_plugins::*,
+    rent::RentMode,
+    rob::*,
     rpc::*,
-    utils::*,
+    snapshot::*,
+    store::*,
+    surfnet::*,
+    utils::check_key_valid,
+    verify::Verification,
+    watch::*,
+    write_set::*,
 };
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use log::warn;
-use mpl_token_metadata::{
-    accounts::{Metadata, TokenRecord},
-    types::TokenState,
-};
+use log::{info, warn};
+use mpl_core::types::{BasePluginAuthority, Creator, Plugin, PluginType, RuleSet, UpdateAuthority};
+use mpl_token_metadata::accounts::{Metadata, TokenRecord};
 use solana_address::Address;
 use solana_pubkey::Pubkey;
 use spl_associated_token_account::get_associated_token_address;
-use spl_token::solana_program::program_option::COption;
 
-mod mpl;
-mod pnft;
+// the rest of the crate refers to these as `crate::*`, same as the modules below
+use nft_robber::{
+    core_account, core_plugins, mpl, pnft, rent, rob, rpc, snapshot, store, surfnet, utils,
+    write_set,
+};
+
+mod balance;
+mod clone;
+mod cluster;
+mod config;
+mod fixtures;
 mod print_plugins;
-mod rpc;
-mod utils;
+#[cfg(test)]
+mod test_support;
+mod verify;
+mod watch;
 
 /// Command line parser using `clap`
 #[derive(Parser, Debug)]
 #[command(name = "core_parser")]
 #[command(about = "NFT robber")]
 struct Cli {
+    /// RPC endpoint, overrides the one in the selected profile
+    #[arg(long, global = true, env = "RPC_URL")]
+    rpc_url: Option<String>,
+    /// Profile from the config file to use
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Config file with the profiles, defaults to `nft_robber.toml`
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Timeout for each RPC request, in seconds
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+    /// How many times failed reads are retried
+    #[arg(long, global = true)]
+    max_retries: Option<u32>,
+    /// Save the accounts a write command touches to this file before changing them, see `restore`.
+    /// Not supported by the surfnet commands
+    #[arg(long, global = true)]
+    snapshot: Option<PathBuf>,
+    /// Allow write commands against RPCs that are not a surfpool fork
+    #[arg(long, global = true)]
+    allow_any_cluster: bool,
+    /// Read and write accounts in this directory of account JSON files instead of going through the RPC.
+    /// Only works for the commands that just read and write accounts (rob, print, unfreeze-core, add-plugin, set-attributes,
+    /// set-royalties, restore, clone, set-balance, dump, load)
+    #[arg(long, global = true)]
+    accounts_dir: Option<PathBuf>,
+    /// What to do with the lamports of accounts whose size changes
+    #[arg(long, global = true, value_enum, default_value_t = RentMode::Adjust)]
+    rent: RentMode,
     #[command(subcommand)]
     command: Commands,
 }
@@ -43,34 +97,265 @@ enum Commands {
     RobCoreNft {
         nft_key: String,
         new_owner: String,
-        #[arg(long)]
+        /// Remove every plugin, so none of the other plugin options can be used with it
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "remove_plugin",
+                "unfreeze",
+                "revoke_freeze_delegate",
+                "keep_plugin_authorities",
+                "plugin_authority",
+            ]
+        )]
         remove_plugins: bool,
+        /// Remove only this plugin, can be repeated (`--remove-plugin freeze-delegate`)
+        #[arg(long = "remove-plugin", value_parser = parse_plugin_type)]
+        remove_plugin: Vec<PluginType>,
+        /// Set `frozen` to false on the freeze delegate plugins
+        #[arg(long)]
+        unfreeze: bool,
+        /// With `--unfreeze`, also give the freeze plugins back to the owner / update authority
+        #[arg(long, requires = "unfreeze")]
+        revoke_freeze_delegate: bool,
+        /// Keep the delegates of the owner managed plugins (transfer, freeze and burn delegate, autograph)
+        /// instead of giving them back to the owner like a real transfer
+        #[arg(long)]
+        keep_plugin_authorities: bool,
+        /// Authority for a plugin, can be repeated: `<plugin type>=<owner|update-authority|none|keep|pubkey>`
+        #[arg(long = "plugin-authority", value_parser = parse_authority_override)]
+        plugin_authority: Vec<(PluginType, Option<BasePluginAuthority>)>,
+        /// Simulate the new owner transferring it back to check the programs accept it. Needs the new owner to have SOL
+        #[arg(long)]
+        verify: bool,
     },
     #[command(about = "Steal a core collection")]
     RobCoreCollection {
         collection_key: String,
         new_authority: String,
+        /// Remove this plugin, can be repeated
+        #[arg(long = "remove-plugin", value_parser = parse_plugin_type)]
+        remove_plugin: Vec<PluginType>,
+    },
+    #[command(about = "Unfreeze a core NFT, keeping its owner and other plugins")]
+    UnfreezeCore {
+        nft_key: String,
+        /// Also give the freeze plugins back to the owner / update authority
+        #[arg(long)]
+        revoke: bool,
+    },
+    #[command(about = "Add a plugin to a core NFT or collection")]
+    AddPlugin {
+        key: String,
+        /// The plugin as JSON, e.g. `{"Attributes": {"attribute_list": [{"key": "level", "value": "1"}]}}`
+        #[arg(long, value_parser = parse_plugin)]
+        plugin: Plugin,
+        /// owner, update-authority, none or a pubkey. Defaults to whoever manages the plugin
+        #[arg(long, value_parser = parse_plugin_authority)]
+        authority: Option<BasePluginAuthority>,
+    },
+    #[command(
+        about = "Add, overwrite or remove traits in the Attributes plugin of a core NFT or collection"
+    )]
+    SetAttributes {
+        key: String,
+        /// `trait=value` pairs to add or overwrite
+        #[arg(value_parser = parse_attribute)]
+        attributes: Vec<(String, String)>,
+        /// Trait to remove, can be repeated
+        #[arg(long)]
+        remove: Vec<String>,
+    },
+    #[command(about = "Edit the Royalties plugin of a core NFT or collection")]
+    SetRoyalties {
+        key: String,
+        #[arg(long)]
+        basis_points: Option<u16>,
+        /// `<pubkey>=<share>`, can be repeated. Replaces all the creators, the shares have to add up to 100
+        #[arg(long = "creator", value_parser = parse_creator)]
+        creators: Vec<Creator>,
+        /// Remove the rule set
+        #[arg(long, conflicts_with_all = ["allow_program", "deny_program"])]
+        no_rule_set: bool,
+        /// Only allow transfers through these programs, can be repeated
+        #[arg(long, conflicts_with = "deny_program")]
+        allow_program: Vec<String>,
+        /// Don't allow transfers through these programs, can be repeated
+        #[arg(long)]
+        deny_program: Vec<String>,
     },
     #[command(about = "Print information for a core NFT")]
     PrintCoreNft { key: String },
     #[command(about = "Print information for a core collection")]
     PrintCoreCollection { key: String },
+    #[command(about = "Find core assets by owner, collection or update authority")]
+    FindCoreAssets {
+        #[arg(long)]
+        owner: Option<String>,
+        /// Both this and `--update-authority` match the update authority field, so only one of them can be used
+        #[arg(long, conflicts_with = "update_authority")]
+        collection: Option<String>,
+        #[arg(long)]
+        update_authority: Option<String>,
+    },
     #[command(about = "Print information for a programmable collection")]
     PrintPNft { mint: String, owner: String },
+    #[command(about = "Print every change to the given accounts")]
+    Watch {
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Defaults to the RPC url with ws:// and the port after the RPC one
+        #[arg(long)]
+        ws_url: Option<String>,
+    },
+    #[command(about = "Write back the accounts saved with --snapshot")]
+    Restore { snapshot: PathBuf },
+    #[command(about = "Surfpool cheatcodes")]
+    Surfnet {
+        #[command(subcommand)]
+        command: SurfnetCommands,
+    },
     #[command(about = "Rob a pNFT")]
     RobPNft {
         mint: String,
         old_owner: String,
         new_owner: String,
+        /// Simulate the new owner transferring it back to check the programs accept it. Needs the new owner to have SOL
+        #[arg(long)]
+        verify: bool,
+    },
+    #[command(about = "Copy accounts from another RPC into the fork")]
+    Clone {
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// RPC to read the accounts from, e.g. mainnet
+        #[arg(long)]
+        from: String,
+        /// Also clone the ATA's mint and token record, the mint's metadata and edition and the collection
+        #[arg(long)]
+        follow: bool,
+    },
+    #[command(about = "Set the SOL balance of an account, creating a wallet if it does not exist")]
+    SetBalance {
+        key: String,
+        /// In SOL, or in lamports with a `lamports` suffix (`1.5`, `1.5sol`, `1500lamports`)
+        #[arg(value_parser = parse_amount)]
+        amount: u64,
+    },
+    #[command(about = "Set the same SOL balance on many accounts")]
+    SetBalances {
+        /// In SOL, or in lamports with a `lamports` suffix (`1.5`, `1.5sol`, `1500lamports`)
+        #[arg(value_parser = parse_amount)]
+        amount: u64,
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    #[command(about = "Save accounts as solana-test-validator account JSON files")]
+    Dump {
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Directory to write `<key>.json` files to
+        #[arg(long)]
+        out: PathBuf,
+    },
+    #[command(about = "Write accounts from solana-test-validator account JSON files")]
+    Load {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
     },
 }
 
-// cursed
-pub fn check_key_valid(key: &str) -> Result<()> {
-    if Pubkey::from_str(&key).is_ok() {
-        Ok(())
-    } else {
-        anyhow::bail!("{} is not a valid key", key);
+impl Commands {
+    /// Commands that write to accounts, these are only allowed on a local fork
+    fn is_mutating(&self) -> bool {
+        match self {
+            Commands::RobCoreNft { .. }
+            | Commands::RobCoreCollection { .. }
+            | Commands::UnfreezeCore { .. }
+            | Commands::AddPlugin { .. }
+            | Commands::SetAttributes { .. }
+            | Commands::SetRoyalties { .. }
+            | Commands::RobPNft { .. }
+            | Commands::Restore { .. }
+            | Commands::Clone { .. }
+            | Commands::SetBalance { .. }
+            | Commands::SetBalances { .. }
+            | Commands::Load { .. } => true,
+            Commands::Surfnet { command } => command.is_mutating(),
+            Commands::PrintCoreNft { .. }
+            | Commands::PrintCoreCollection { .. }
+            | Commands::FindCoreAssets { .. }
+            | Commands::Watch { .. }
+            | Commands::PrintPNft { .. }
+            | Commands::Dump { .. } => false,
+        }
+    }
+
+    /// Commands that only read and write accounts, so they can run on any `AccountStore`
+    fn only_uses_accounts(&self) -> bool {
+        match self {
+            Commands::RobCoreNft { .. }
+            | Commands::RobCoreCollection { .. }
+            | Commands::UnfreezeCore { .. }
+            | Commands::AddPlugin { .. }
+            | Commands::SetAttributes { .. }
+            | Commands::SetRoyalties { .. }
+            | Commands::RobPNft { .. }
+            | Commands::Restore { .. }
+            | Commands::Clone { .. }
+            | Commands::SetBalance { .. }
+            | Commands::SetBalances { .. }
+            | Commands::Dump { .. }
+            | Commands::Load { .. }
+            | Commands::PrintCoreNft { .. }
+            | Commands::PrintCoreCollection { .. }
+            | Commands::PrintPNft { .. } => true,
+            Commands::FindCoreAssets { .. } | Commands::Watch { .. } | Commands::Surfnet { .. } => {
+                false
+            }
+        }
+    }
+
+    fn verify(&self) -> bool {
+        matches!(
+            self,
+            Commands::RobCoreNft { verify: true, .. } | Commands::RobPNft { verify: true, .. }
+        )
+    }
+
+    /// The transfer to simulate once the command is done. Has to be called before the command runs,
+    /// since it needs to know the old owner
+    async fn verification(&self, rpc: &Rpc) -> Result<Option<Verification>> {
+        let verification = match self {
+            Commands::RobCoreNft {
+                nft_key,
+                new_owner,
+                verify: true,
+                ..
+            } => {
+                let Some(asset) = rpc.get_account_info(nft_key).await? else {
+                    anyhow::bail!("NFT account did not exist!");
+                };
+                Some(Verification::CoreAsset {
+                    asset: nft_key.clone(),
+                    new_owner: new_owner.clone(),
+                    destination: deser_asset_header(&asset.data)?.owner.to_string(),
+                })
+            }
+            Commands::RobPNft {
+                mint,
+                old_owner,
+                new_owner,
+                verify: true,
+            } => Some(Verification::PNft {
+                mint: mint.clone(),
+                new_owner: new_owner.clone(),
+                destination: old_owner.clone(),
+            }),
+            _ => None,
+        };
+
+        Ok(verification)
     }
 }
 
@@ -81,90 +366,267 @@ async fn main() -> Result<()> {
     }
     env_logger::init();
 
-    let rpc = Rpc::new("http://localhost:8899".into());
-
     let cli = Cli::parse();
 
+    let config = match &cli.config {
+        Some(path) => Config::load(path, true)?,
+        None => Config::load(Path::new(DEFAULT_CONFIG_PATH), false)?,
+    };
+    let rpc_settings = config.resolve(
+        cli.profile.as_deref(),
+        Overrides {
+            rpc_url: cli.rpc_url.clone(),
+            timeout_secs: cli.timeout,
+            max_retries: cli.max_retries,
+        },
+    )?;
+
+    // the cheatcodes change accounts inside surfpool, we never see which ones or what they were before
+    if cli.snapshot.is_some()
+        && matches!(cli.command, Commands::Surfnet { .. })
+        && cli.command.is_mutating()
+    {
+        anyhow::bail!(
+            "--snapshot does not work with surfnet commands, their changes can not be restored"
+        );
+    }
+
+    if let Some(dir) = &cli.accounts_dir {
+        if !cli.command.only_uses_accounts() {
+            anyhow::bail!("This command needs an RPC, it does not work with --accounts-dir");
+        }
+        if cli.command.verify() {
+            anyhow::bail!("--verify simulates a transaction, it does not work with --accounts-dir");
+        }
+        let store = DirStore::new(dir)?;
+        return run_account_command(
+            &store,
+            cli.command,
+            cli.snapshot.as_deref(),
+            cli.rent,
+            &rpc_settings.retry_policy,
+        )
+        .await;
+    }
+
+    info!("Using RPC {}", rpc_settings.url);
+
+    let rpc = Rpc::new(
+        rpc_settings.url,
+        &rpc_settings.headers,
+        rpc_settings.retry_policy.clone(),
+    )?;
+
+    if cli.command.is_mutating() {
+        ensure_local_fork(&rpc, cli.allow_any_cluster).await?;
+    }
+
+    if cli.command.only_uses_accounts() {
+        let verification = cli.command.verification(&rpc).await?;
+        run_account_command(
+            &rpc,
+            cli.command,
+            cli.snapshot.as_deref(),
+            cli.rent,
+            &rpc_settings.retry_policy,
+        )
+        .await?;
+        if let Some(verification) = verification {
+            verification.run(&rpc).await?;
+        }
+        return Ok(());
+    }
+
     match cli.command {
+        Commands::FindCoreAssets {
+            owner,
+            collection,
+            update_authority,
+        } => {
+            let parse = |key: &str| -> Result<Pubkey> {
+                check_key_valid(key)?;
+                Ok(Pubkey::from_str(key)?)
+            };
+            let owner = owner.as_deref().map(parse).transpose()?;
+            let update_authority = match (collection, update_authority) {
+                (Some(collection), _) => Some(UpdateAuthority::Collection(parse(&collection)?)),
+                (None, Some(update_authority)) => {
+                    Some(UpdateAuthority::Address(parse(&update_authority)?))
+                }
+                (None, None) => None,
+            };
+
+            if owner.is_none() && update_authority.is_none() {
+                anyhow::bail!("Need at least one of --owner, --collection or --update-authority");
+            }
+            let filters = core_asset_filters(owner.as_ref(), update_authority.as_ref())?;
+
+            let assets = rpc
+                .get_program_accounts(
+                    &mpl_core::ID.to_string(),
+                    &GetProgramAccountsConfig {
+                        filters,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+            println!("Found {} assets", assets.len());
+            for (pubkey, account) in assets {
+                let asset_header = deser_asset_header(&account.data)?;
+                println!("{}: {:#?}", pubkey, asset_header);
+            }
+        }
+        Commands::Watch { keys, ws_url } => {
+            for key in &keys {
+                check_key_valid(key)?;
+            }
+
+            let ws_url = match ws_url {
+                Some(ws_url) => ws_url,
+                None => ws_url_from_http(&rpc.url)?,
+            };
+
+            watch_accounts(&rpc, &ws_url, &keys).await?;
+        }
+        Commands::Surfnet { command } => {
+            run_surfnet_command(&rpc, command).await?;
+        }
+        _ => unreachable!("account commands are handled above"),
+    }
+
+    Ok(())
+}
+
+/// Runs the commands that only read and write accounts against any account store.
+/// `retry_policy` is for the other RPCs some commands read from (`clone --from`)
+async fn run_account_command<S: AccountStore>(
+    store: &S,
+    command: Commands,
+    snapshot_path: Option<&Path>,
+    rent: RentMode,
+    retry_policy: &RetryPolicy,
+) -> Result<()> {
+    match command {
         Commands::RobCoreNft {
             nft_key,
             new_owner,
             remove_plugins,
+            remove_plugin,
+            unfreeze,
+            revoke_freeze_delegate,
+            keep_plugin_authorities,
+            plugin_authority,
+            verify: _,
         } => {
             check_key_valid(&nft_key)?;
             check_key_valid(&new_owner)?;
 
-            if let Some(account_info_response) = rpc.get_account_info(&nft_key).await? {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                // another WARN: the deserialization the metaplex crate does will only deserialize the header
-                // this means that if you just write the header you will be deleting all the other data in the NFT, like plugins
-                let mut asset_data = b64_to_bytes(&account_info_response.data[0])?;
-                let mut asset_header = deser_asset_header(&asset_data)?;
-                asset_header.owner = Pubkey::from_str(&new_owner)?;
-
-                // see the warning above. need to keep the remaining data intact, so just copy the header
-                // the header length does not change when changing the owner etc
-                let new_header_data = ser_asset_header(&asset_header)?;
-                asset_data[..new_header_data.len()].copy_from_slice(&new_header_data);
-                
-                if remove_plugins {
-                    // truncate all bytes after the header, effectively removing plugins
-                    asset_data.truncate(new_header_data.len());
-                }
-
-                let data_string = bytes_to_hex(&asset_data)?;
-
-                let set_account_info = SetAccountInfo {
-                    data: Some(data_string),
-                    executable: account_info_response.executable,
-                    lamports: account_info_response.lamports,
-                    owner: account_info_response.owner,
-                    rent_epoch: account_info_response.rent_epoch,
-                };
-
-                rpc.set_account_info(&nft_key, &set_account_info).await?;
-            } else {
-                anyhow::bail!("NFT account did not exist!");
-            }
+            rob_core_nft(
+                store,
+                &nft_key,
+                &new_owner,
+                &PluginChanges {
+                    remove_all: remove_plugins,
+                    remove: remove_plugin,
+                    unfreeze,
+                    revoke_freeze_delegate,
+                    reset_owner_managed: !keep_plugin_authorities,
+                    authorities: plugin_authority,
+                },
+                rent,
+                snapshot_path,
+            )
+            .await?;
         }
         Commands::RobCoreCollection {
             collection_key,
             new_authority,
+            remove_plugin,
         } => {
             check_key_valid(&collection_key)?;
             check_key_valid(&new_authority)?;
 
-            if let Some(account_info_response) = rpc.get_account_info(&collection_key).await? {
-                let mut collection_data = b64_to_bytes(&account_info_response.data[0])?;
-                let mut collection_header = deser_collection_header(&collection_data)?;
-                collection_header.update_authority = Pubkey::from_str(&new_authority)?;
-
-                let new_header_data = ser_collection_header(&collection_header)?;
-                collection_data[..new_header_data.len()].copy_from_slice(&new_header_data);
+            rob_core_collection(
+                store,
+                &collection_key,
+                &new_authority,
+                &PluginChanges {
+                    remove: remove_plugin,
+                    ..Default::default()
+                },
+                rent,
+                snapshot_path,
+            )
+            .await?;
+        }
+        Commands::UnfreezeCore { nft_key, revoke } => {
+            check_key_valid(&nft_key)?;
 
-                let data_string = bytes_to_hex(&collection_data)?;
+            unfreeze_core_nft(store, &nft_key, revoke, rent, snapshot_path).await?;
+        }
+        Commands::AddPlugin {
+            key,
+            plugin,
+            authority,
+        } => {
+            check_key_valid(&key)?;
 
-                let set_account_info = SetAccountInfo {
-                    data: Some(data_string),
-                    executable: account_info_response.executable,
-                    lamports: account_info_response.lamports,
-                    owner: account_info_response.owner,
-                    rent_epoch: account_info_response.rent_epoch,
-                };
+            add_core_plugin(store, &key, plugin, authority, rent, snapshot_path).await?;
+        }
+        Commands::SetAttributes {
+            key,
+            attributes,
+            remove,
+        } => {
+            check_key_valid(&key)?;
+            if attributes.is_empty() && remove.is_empty() {
+                anyhow::bail!("Nothing to set or remove");
+            }
 
-                rpc.set_account_info(&collection_key, &set_account_info)
-                    .await?;
+            set_core_attributes(store, &key, &attributes, &remove, rent, snapshot_path).await?;
+        }
+        Commands::SetRoyalties {
+            key,
+            basis_points,
+            creators,
+            no_rule_set,
+            allow_program,
+            deny_program,
+        } => {
+            check_key_valid(&key)?;
+            let parse_programs = |programs: &[String]| -> Result<Vec<Pubkey>> {
+                programs
+                    .iter()
+                    .map(|program| {
+                        check_key_valid(program)?;
+                        Ok(Pubkey::from_str(program)?)
+                    })
+                    .collect()
+            };
+
+            let rule_set = if no_rule_set {
+                Some(RuleSet::None)
+            } else if !allow_program.is_empty() {
+                Some(RuleSet::ProgramAllowList(parse_programs(&allow_program)?))
+            } else if !deny_program.is_empty() {
+                Some(RuleSet::ProgramDenyList(parse_programs(&deny_program)?))
             } else {
-                anyhow::bail!("Collection account did not exist!");
-            }
+                None
+            };
+            let changes = RoyaltiesChanges {
+                basis_points,
+                creators: (!creators.is_empty()).then_some(creators),
+                rule_set,
+            };
+
+            set_core_royalties(store, &key, &changes, rent, snapshot_path).await?;
         }
         Commands::PrintCoreNft { key } => {
             check_key_valid(&key)?;
 
-            if let Some(account_info_response) = rpc.get_account_info(&key).await? {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                let asset_data = b64_to_bytes(&account_info_response.data[0])?;
-                print_asset_info(&asset_data)?;
+            if let Some(account_info_response) = store.get(&key).await? {
+                print_asset_info(&account_info_response.data)?;
             } else {
                 anyhow::bail!("NFT account did not exist!");
             }
@@ -172,10 +634,8 @@ async fn main() -> Result<()> {
         Commands::PrintCoreCollection { key } => {
             check_key_valid(&key)?;
 
-            if let Some(account_info_response) = rpc.get_account_info(&key).await? {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                let asset_data = b64_to_bytes(&account_info_response.data[0])?;
-                print_collection_info(&asset_data)?;
+            if let Some(account_info_response) = store.get(&key).await? {
+                print_collection_info(&account_info_response.data)?;
             } else {
                 anyhow::bail!("NFT account did not exist!");
             }
@@ -195,144 +655,135 @@ async fn main() -> Result<()> {
             let token_record_account = TokenRecord::find_pda(&mint_key, &ata_key).0;
             let metadata_account = Metadata::find_pda(&mint_key).0;
 
+            let [
+                ata_account,
+                token_record_account_info,
+                metadata_account_info,
+            ]: [_; 3] = store
+                .get_many(&[
+                    ata_addr.to_string(),
+                    token_record_account.to_string(),
+                    metadata_account.to_string(),
+                ])
+                .await?
+                .try_into()
+                .expect("get_many returns one entry per key");
+
             println!("ATA is {}:", ata_addr);
-            if let Some(account_info_response) = rpc.get_account_info(&ata_addr.to_string()).await?
-            {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                let ata_data = b64_to_bytes(&account_info_response.data[0])?;
-                print_ata(&ata_data)?;
+            if let Some(account_info_response) = ata_account {
+                print_ata(&account_info_response.data)?;
             } else {
                 anyhow::bail!("ATA account did not exist!");
             }
 
             println!("TRA is {}:", token_record_account);
-            if let Some(account_info_response) = rpc
-                .get_account_info(&token_record_account.to_string())
-                .await?
-            {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                let ata_data = b64_to_bytes(&account_info_response.data[0])?;
-                print_token_record(&ata_data)?;
+            if let Some(account_info_response) = token_record_account_info {
+                print_token_record(&account_info_response.data)?;
             } else {
-                anyhow::bail!("ATA account did not exist!");
+                anyhow::bail!("TRA account did not exist!");
             }
 
             println!("Metadata is {}:", metadata_account);
-            if let Some(account_info_response) =
-                rpc.get_account_info(&metadata_account.to_string()).await?
-            {
-                // WARN: I assume data is [data, "base64"], and that the format is base64
-                let ata_data = b64_to_bytes(&account_info_response.data[0])?;
-                print_metadata(&ata_data)?;
+            if let Some(account_info_response) = metadata_account_info {
+                print_metadata(&account_info_response.data)?;
             } else {
-                anyhow::bail!("ATA account did not exist!");
+                anyhow::bail!("Metadata account did not exist!");
             }
         }
+        Commands::Restore { snapshot } => {
+            let write_set = Snapshot::load(&snapshot)?.restore_write_set()?;
+
+            println!("Restoring {} accounts", write_set.pubkeys().len());
+            write_set.apply(store, snapshot_path).await?;
+        }
         Commands::RobPNft {
             mint,
             old_owner,
             new_owner,
+            verify: _,
         } => {
             check_key_valid(&mint)?;
             check_key_valid(&old_owner)?;
             check_key_valid(&new_owner)?;
 
-            let mint_addr = Address::from_str(&mint)?;
-            let mint_key = Pubkey::new_from_array(mint_addr.to_bytes());
+            rob_pnft(store, &mint, &old_owner, &new_owner, rent, snapshot_path).await?;
+        }
+        Commands::Clone { keys, from, follow } => {
+            for key in &keys {
+                check_key_valid(key)?;
+            }
 
-            let old_owner_addr = Address::from_str(&old_owner)?;
-            let new_owner_addr = Address::from_str(&new_owner)?;
-
-            let old_ata_addr = get_associated_token_address(&old_owner_addr, &mint_addr);
-            let new_ata_addr = get_associated_token_address(&new_owner_addr, &mint_addr);
-            let old_ata_key = Pubkey::new_from_array(old_ata_addr.to_bytes());
-            let new_ata_key = Pubkey::new_from_array(new_ata_addr.to_bytes());
-
-            let old_tra_key = TokenRecord::find_pda(&mint_key, &old_ata_key).0;
-            let new_tra_key = TokenRecord::find_pda(&mint_key, &new_ata_key).0;
-
-            let new_tra_pda = Pubkey::find_program_address(
-                &[
-                    b"metadata",
-                    mpl_token_metadata::ID.as_array(),
-                    mint_key.as_array(),
-                    b"token_record",
-                    new_ata_key.as_array(),
-                ],
-                &mpl_token_metadata::ID,
-            );
-
-            let old_ata_account = rpc
-                .get_account_info(&old_ata_addr.to_string())
-                .await?
-                .expect("old_ata does not exist");
-            let old_tra_account = rpc
-                .get_account_info(&old_tra_key.to_string())
-                .await?
-                .expect("old_tra does not exist");
-
-            println!("Old ATA: {}", old_ata_key);
-            println!("New ATA: {}", new_ata_key);
-            println!("Old TRA: {}", old_tra_key);
-            println!("New TRA: {}", new_tra_key);
-
-            // Close old ATA and TRA
-            println!("Closing old accounts");
-            rpc.close_account(&old_ata_key.to_string()).await?;
-            rpc.close_account(&old_tra_key.to_string()).await?;
-
-            // Create new ones. Everything is cloned except the `owner` and `delegate` field of the ATA
-            println!("Deserializing ATA");
-            // remove delegation from the ATA and set the owner to the new owner
-            let mut ata_info = deser_ata(&b64_to_bytes(&old_ata_account.data[0])?)?;
-            ata_info.owner = new_owner_addr;
-            ata_info.delegate = COption::None;
-
-            println!("Deserializing TRA");
-            // completely unfreeze the pNFT (the ATA remains frozen), and remove delegation
-            let mut tra_info = deser_token_record(&b64_to_bytes(&old_tra_account.data[0])?)?;
-            tra_info.state = TokenState::Unlocked;
-            tra_info.bump = new_tra_pda.1;
-            tra_info.rule_set_revision = None;
-            tra_info.delegate = None;
-            tra_info.delegate_role = None;
-            tra_info.locked_transfer = None;
-
-            println!("Serializing ATA");
-            let ata_bytes = ser_ata(&ata_info)?;
-            println!("Serializing TRA");
-            let tra_bytes = ser_token_record(&tra_info)?;
-
-            let debug_tra = deser_token_record(&tra_bytes)?;
-            println!("debug: {:#?}", debug_tra);
-
-            println!("Setting ATA");
-            rpc.set_account_info(
-                &new_ata_key.to_string(),
-                &SetAccountInfo {
-                    data: Some(bytes_to_hex(&ata_bytes)?),
-                    executable: old_ata_account.executable,
-                    lamports: old_ata_account.lamports,
-                    owner: old_ata_account.owner,
-                    rent_epoch: old_ata_account.rent_epoch,
-                },
-            )
-            .await?;
+            // no headers, those are for the profile's RPC and shouldn't leak to another endpoint
+            let source = Rpc::new(from, &HashMap::new(), retry_policy.clone())?;
+            clone_accounts(&source, store, &keys, follow, snapshot_path).await?;
+        }
+        Commands::SetBalance { key, amount } => {
+            check_key_valid(&key)?;
 
-            println!("Setting TRA");
-            rpc.set_account_info(
-                &new_tra_key.to_string(),
-                &SetAccountInfo {
-                    data: Some(bytes_to_hex(&tra_bytes)?),
-                    executable: old_tra_account.executable,
-                    lamports: old_tra_account.lamports,
-                    owner: old_tra_account.owner,
-                    rent_epoch: old_tra_account.rent_epoch,
-                },
-            )
-            .await?;
+            set_balances(store, &[key], amount, snapshot_path).await?;
+        }
+        Commands::SetBalances { amount, keys } => {
+            for key in &keys {
+                check_key_valid(key)?;
+            }
+
+            set_balances(store, &keys, amount, snapshot_path).await?;
+        }
+        Commands::Dump { keys, out } => {
+            for key in &keys {
+                check_key_valid(key)?;
+            }
+
+            dump_accounts(store, &keys, &out).await?;
+        }
+        Commands::Load { files } => {
+            load_accounts(store, &files, snapshot_path).await?;
+        }
+        Commands::FindCoreAssets { .. } | Commands::Watch { .. } | Commands::Surfnet { .. } => {
+            unreachable!("not an account command")
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    const ASSET: &str = "11111111111111111111111111111112";
+    const OWNER: &str = "11111111111111111111111111111113";
+
+    #[test]
+    fn cli_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn remove_plugins_conflicts_with_plugin_edits() {
+        let rob = |extra: &[&str]| {
+            let mut args = vec![
+                "nft_robber",
+                "rob-core-nft",
+                ASSET,
+                OWNER,
+                "--remove-plugins",
+            ];
+            args.extend_from_slice(extra);
+            Cli::try_parse_from(args)
+        };
+
+        assert!(rob(&[]).is_ok());
+        for extra in [
+            &["--remove-plugin", "freeze-delegate"][..],
+            &["--unfreeze"],
+            &["--unfreeze", "--revoke-freeze-delegate"],
+            &["--keep-plugin-authorities"],
+            &["--plugin-authority", "attributes=owner"],
+        ] {
+            assert!(rob(extra).is_err(), "{:?}", extra);
+        }
+    }
+}
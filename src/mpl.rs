@@ -1,5 +1,16 @@
 use anyhow::Result;
-use mpl_core::accounts::{BaseAssetV1, BaseCollectionV1};
+use mpl_core::{
+    accounts::{BaseAssetV1, BaseCollectionV1},
+    types::{Key, UpdateAuthority},
+};
+use solana_pubkey::Pubkey;
+
+use crate::rpc::RpcFilter;
+
+/// Offsets into `BaseAssetV1`, the fields before these are all fixed size so they never move
+pub const ASSET_KEY_OFFSET: usize = 0;
+pub const ASSET_OWNER_OFFSET: usize = 1;
+pub const ASSET_UPDATE_AUTHORITY_OFFSET: usize = 33;
 
 pub fn deser_collection_header(bytes: &[u8]) -> Result<BaseCollectionV1> {
     Ok(BaseCollectionV1::from_bytes(bytes)?)
@@ -16,3 +27,124 @@ pub fn ser_collection_header(header: &BaseCollectionV1) -> Result<Vec<u8>> {
 pub fn ser_asset_header(header: &BaseAssetV1) -> Result<Vec<u8>> {
     Ok(borsh::to_vec(header)?)
 }
+
+/// Bytes of the update authority as they are in the asset header, enum variant included
+pub fn ser_update_authority(update_authority: &UpdateAuthority) -> Result<Vec<u8>> {
+    Ok(borsh::to_vec(update_authority)?)
+}
+
+/// `getProgramAccounts` filters for the assets with `owner` and/or `update_authority`
+pub fn core_asset_filters(
+    owner: Option<&Pubkey>,
+    update_authority: Option<&UpdateAuthority>,
+) -> Result<Vec<RpcFilter>> {
+    let mut filters = vec![RpcFilter::memcmp(ASSET_KEY_OFFSET, &[Key::AssetV1 as u8])];
+
+    if let Some(owner) = owner {
+        filters.push(RpcFilter::memcmp(ASSET_OWNER_OFFSET, owner.as_ref()));
+    }
+
+    if let Some(update_authority) = update_authority {
+        filters.push(RpcFilter::memcmp(
+            ASSET_UPDATE_AUTHORITY_OFFSET,
+            &ser_update_authority(update_authority)?,
+        ));
+    }
+
+    Ok(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        rpc::GetProgramAccountsConfig,
+        test_support::core_asset,
+        utils::{b58_to_bytes, bytes_to_b58},
+    };
+
+    fn owner() -> Pubkey {
+        Pubkey::new_from_array([7; 32])
+    }
+
+    fn collection() -> UpdateAuthority {
+        UpdateAuthority::Collection(Pubkey::new_from_array([9; 32]))
+    }
+
+    #[test]
+    fn filters_serialize_like_solana_rpc_expects() {
+        let config = GetProgramAccountsConfig {
+            filters: core_asset_filters(Some(&owner()), Some(&collection())).unwrap(),
+            ..Default::default()
+        };
+
+        // `Collection` is variant 2, followed by the key
+        let mut update_authority = vec![2];
+        update_authority.extend_from_slice(&[9; 32]);
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            json!({
+                "encoding": "base64",
+                "filters": [
+                    // `Key::AssetV1` is 1, "2" in base58
+                    { "memcmp": { "offset": 0, "bytes": "2" } },
+                    { "memcmp": { "offset": 1, "bytes": owner().to_string() } },
+                    { "memcmp": { "offset": 33, "bytes": bytes_to_b58(&update_authority) } },
+                ],
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(RpcFilter::DataSize(165)).unwrap(),
+            json!({ "dataSize": 165 })
+        );
+    }
+
+    #[test]
+    fn filters_match_a_serialized_asset() {
+        for update_authority in [
+            collection(),
+            UpdateAuthority::Address(Pubkey::new_from_array([8; 32])),
+        ] {
+            let mut asset = core_asset(owner());
+            asset.header.update_authority = update_authority.clone();
+            let data = ser_asset_header(&asset.header).unwrap();
+
+            let filters = core_asset_filters(Some(&owner()), Some(&update_authority)).unwrap();
+            assert_eq!(filters.len(), 3);
+            for filter in filters {
+                let RpcFilter::Memcmp(memcmp) = filter else {
+                    panic!("only memcmp filters are used");
+                };
+                let bytes = b58_to_bytes(&memcmp.bytes).unwrap();
+                assert_eq!(
+                    &data[memcmp.offset..memcmp.offset + bytes.len()],
+                    &bytes[..],
+                    "filter at offset {}",
+                    memcmp.offset
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn filters_only_match_their_update_authority() {
+        let mut asset = core_asset(owner());
+        asset.header.update_authority = collection();
+        let data = ser_asset_header(&asset.header).unwrap();
+
+        // same key as the collection, but an address
+        let address = UpdateAuthority::Address(Pubkey::new_from_array([9; 32]));
+        let filters = core_asset_filters(None, Some(&address)).unwrap();
+        let RpcFilter::Memcmp(memcmp) = &filters[1] else {
+            panic!("only memcmp filters are used");
+        };
+        let bytes = b58_to_bytes(&memcmp.bytes).unwrap();
+        assert_ne!(
+            &data[memcmp.offset..memcmp.offset + bytes.len()],
+            &bytes[..]
+        );
+    }
+}
@@ -68,19 +68,31 @@ pub fn _ser_metadata(metadata: &Metadata) -> Result<Vec<u8>> {
 }
 
 pub fn print_ata(bytes: &[u8]) -> Result<()> {
-    let ata = deser_ata(bytes)?;
-    println!("{:#?}", ata);
+    println!("{}", format_ata(bytes)?);
     Ok(())
 }
 
+pub fn format_ata(bytes: &[u8]) -> Result<String> {
+    let ata = deser_ata(bytes)?;
+    Ok(format!("{:#?}", ata))
+}
+
 pub fn print_token_record(bytes: &[u8]) -> Result<()> {
-    let tra = deser_token_record(bytes)?;
-    println!("{:#?}", tra);
+    println!("{}", format_token_record(bytes)?);
     Ok(())
 }
 
+pub fn format_token_record(bytes: &[u8]) -> Result<String> {
+    let tra = deser_token_record(bytes)?;
+    Ok(format!("{:#?}", tra))
+}
+
 pub fn print_metadata(bytes: &[u8]) -> Result<()> {
-    let meta = deser_metadata(bytes)?;
-    println!("{:#?}", meta);
+    println!("{}", format_metadata(bytes)?);
     Ok(())
 }
+
+pub fn format_metadata(bytes: &[u8]) -> Result<String> {
+    let meta = deser_metadata(bytes)?;
+    Ok(format!("{:#?}", meta))
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use log::{info, warn};
+
+use crate::{rpc::*, utils::bytes_to_hex};
+
+// same values as the default `Rent` sysvar, which is what mainnet and surfpool use
+pub const LAMPORTS_PER_BYTE_YEAR: u64 = 3480;
+pub const EXEMPTION_THRESHOLD_YEARS: u64 = 2;
+/// Bytes every account is charged for on top of its data
+pub const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
+
+/// Same as `getMinimumBalanceForRentExemption`, without asking the RPC
+pub fn minimum_balance(data_len: usize) -> u64 {
+    (ACCOUNT_STORAGE_OVERHEAD + data_len as u64)
+        * LAMPORTS_PER_BYTE_YEAR
+        * EXEMPTION_THRESHOLD_YEARS
+}
+
+/// What to do with the lamports of an account whose data changes size
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RentMode {
+    /// Add or remove the rent difference, and top up to the rent-exempt minimum
+    #[default]
+    Adjust,
+    /// Keep the lamports, only warn if the account is not rent exempt anymore
+    Warn,
+}
+
+/// Lamports an account with `lamports` should have after its data goes from `old_len` to `new_len` bytes.
+/// With `Adjust`, lamports above the rent-exempt minimum are kept. Nothing changes if the size doesn't
+pub fn lamports_for_size(
+    pubkey: &str,
+    lamports: u64,
+    old_len: usize,
+    new_len: usize,
+    mode: RentMode,
+) -> u64 {
+    if old_len == new_len {
+        return lamports;
+    }
+    let minimum = minimum_balance(new_len);
+
+    match mode {
+        RentMode::Adjust => {
+            let adjusted = lamports
+                .saturating_add(minimum)
+                .saturating_sub(minimum_balance(old_len))
+                .max(minimum);
+            if adjusted != lamports {
+                info!(
+                    "{} is now {} bytes, setting lamports from {} to {}",
+                    pubkey, new_len, lamports, adjusted
+                );
+            }
+            adjusted
+        }
+        RentMode::Warn => {
+            if lamports < minimum {
+                warn!(
+                    "{} needs {} lamports to be rent exempt with {} bytes but only has {}",
+                    pubkey, minimum, new_len, lamports
+                );
+            }
+            lamports
+        }
+    }
+}
+
+/// `account` with its data replaced by `data` and the lamports fixed up for the new size
+pub fn set_data(
+    pubkey: &str,
+    account: &GetAccountInfoResponse,
+    data: &[u8],
+    mode: RentMode,
+) -> Result<SetAccountInfo> {
+    Ok(SetAccountInfo {
+        data: Some(bytes_to_hex(data)?),
+        executable: account.executable,
+        lamports: lamports_for_size(
+            pubkey,
+            account.lamports,
+            account.data.len(),
+            data.len(),
+            mode,
+        ),
+        owner: account.owner.clone(),
+        rent_epoch: account.rent_epoch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimum_balance_matches_the_cluster() {
+        assert_eq!(minimum_balance(0), 890_880);
+        // token account
+        assert_eq!(minimum_balance(165), 2_039_280);
+    }
+
+    #[test]
+    fn adjust_keeps_extra_lamports() {
+        let extra = 1_000;
+        let lamports = lamports_for_size(
+            "key",
+            minimum_balance(200) + extra,
+            200,
+            100,
+            RentMode::Adjust,
+        );
+        assert_eq!(lamports, minimum_balance(100) + extra);
+
+        let lamports = lamports_for_size(
+            "key",
+            minimum_balance(100) + extra,
+            100,
+            200,
+            RentMode::Adjust,
+        );
+        assert_eq!(lamports, minimum_balance(200) + extra);
+    }
+
+    #[test]
+    fn adjust_tops_up_to_rent_exempt() {
+        assert_eq!(
+            lamports_for_size("key", 1, 100, 101, RentMode::Adjust),
+            minimum_balance(101)
+        );
+        assert_eq!(
+            lamports_for_size("key", u64::MAX, 100, 200, RentMode::Adjust),
+            u64::MAX - minimum_balance(100)
+        );
+    }
+
+    #[test]
+    fn same_size_keeps_lamports() {
+        assert_eq!(lamports_for_size("key", 1, 100, 100, RentMode::Adjust), 1);
+    }
+
+    #[test]
+    fn warn_keeps_lamports() {
+        assert_eq!(lamports_for_size("key", 1, 100, 200, RentMode::Warn), 1);
+    }
+}
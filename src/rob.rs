@@ -0,0 +1,707 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use log::{debug, info, warn};
+use mpl_core::types::{BasePluginAuthority, PluginType};
+use mpl_token_metadata::{accounts::TokenRecord, types::TokenState};
+use solana_address::Address;
+use solana_pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::solana_program::program_option::COption;
+
+use crate::{
+    core_account::{
+        CoreAccount, CoreAsset, CoreCollection, CoreHeader, PluginEntry, is_owner_managed,
+    },
+    pnft::{deser_ata, deser_token_record, ser_ata, ser_token_record},
+    rent::{RentMode, set_data},
+    store::AccountStore,
+    write_set::*,
+};
+
+fn set_authority(entry: &mut PluginEntry, authority: BasePluginAuthority) {
+    if entry.authority != authority {
+        info!(
+            "Setting the authority of {:?} from {:?} to {:?}",
+            PluginType::from(&entry.plugin),
+            entry.authority,
+            authority
+        );
+        entry.authority = authority;
+    }
+}
+
+/// What to do with the plugins of a core asset or collection that is being robbed
+#[derive(Debug, Clone, Default)]
+pub struct PluginChanges {
+    /// Drop every plugin, external ones included
+    pub remove_all: bool,
+    /// Drop only these
+    pub remove: Vec<PluginType>,
+    /// Set `frozen` to false on the freeze plugins
+    pub unfreeze: bool,
+    /// Also give the freeze plugins back to their manager, see `CoreAccount::unfreeze`
+    pub revoke_freeze_delegate: bool,
+    /// Give owner managed plugins back to the owner, like a real transfer does
+    pub reset_owner_managed: bool,
+    /// Authority for these plugins, `None` keeps the one from before. Applied last, so it takes priority over
+    /// `reset_owner_managed` and `revoke_freeze_delegate`
+    pub authorities: Vec<(PluginType, Option<BasePluginAuthority>)>,
+}
+
+impl PluginChanges {
+    pub fn apply<H>(&self, account: &mut CoreAccount<H>) -> Result<()> {
+        if self.remove_all {
+            account.plugins.clear();
+            account.unknown_plugins.clear();
+            account.external_plugins.clear();
+            account.has_registry = false;
+            return Ok(());
+        }
+
+        for plugin_type in &self.remove {
+            if !account.remove_plugin(plugin_type.clone()) {
+                anyhow::bail!("There is no {:?} plugin to remove", plugin_type);
+            }
+        }
+
+        for (i, (plugin_type, _)) in self.authorities.iter().enumerate() {
+            if self.authorities[..i]
+                .iter()
+                .any(|(earlier, _)| earlier == plugin_type)
+            {
+                anyhow::bail!("The authority of {:?} is set more than once", plugin_type);
+            }
+            if !account
+                .plugins
+                .iter()
+                .any(|entry| PluginType::from(&entry.plugin) == *plugin_type)
+            {
+                anyhow::bail!(
+                    "There is no {:?} plugin to set the authority of",
+                    plugin_type
+                );
+            }
+        }
+
+        // what `keep` goes back to, after the reset and unfreeze below
+        let original_authorities: Vec<BasePluginAuthority> = account
+            .plugins
+            .iter()
+            .map(|entry| entry.authority.clone())
+            .collect();
+
+        if self.reset_owner_managed {
+            for entry in &mut account.plugins {
+                if is_owner_managed(&PluginType::from(&entry.plugin)) {
+                    set_authority(entry, BasePluginAuthority::Owner);
+                }
+            }
+        }
+
+        if self.unfreeze && !account.unfreeze(self.revoke_freeze_delegate) {
+            warn!("There is no freeze plugin to unfreeze");
+        }
+
+        // explicit authorities go last so nothing above can change them
+        for (entry, original) in account.plugins.iter_mut().zip(original_authorities) {
+            let plugin_type = PluginType::from(&entry.plugin);
+            if let Some((_, authority)) = self
+                .authorities
+                .iter()
+                .find(|(overridden, _)| *overridden == plugin_type)
+            {
+                set_authority(entry, authority.clone().unwrap_or(original));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the account at `key`, lets `rewrite` turn its data into new data and writes that back,
+/// with the lamports fixed up for the new size. Every edit of a core account goes through this
+pub async fn rewrite_core_account<S: AccountStore>(
+    store: &S,
+    key: &str,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+    rewrite: impl FnOnce(&[u8]) -> Result<Vec<u8>>,
+) -> Result<()> {
+    let Some(account_info_response) = store.get(key).await? else {
+        anyhow::bail!("{} did not exist!", key);
+    };
+
+    let data = rewrite(&account_info_response.data)?;
+    let set_account_info = set_data(key, &account_info_response, &data, rent)?;
+
+    let mut write_set = WriteSet::new();
+    write_set.set(key, set_account_info);
+    write_set.apply(store, snapshot).await?;
+
+    Ok(())
+}
+
+/// Reads the core asset or collection at `key`, lets `edit` change it and writes it back
+pub async fn edit_core_account<S, H>(
+    store: &S,
+    key: &str,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+    edit: impl FnOnce(&mut CoreAccount<H>) -> Result<()>,
+) -> Result<()>
+where
+    S: AccountStore,
+    H: CoreHeader,
+{
+    rewrite_core_account(store, key, rent, snapshot, |data| {
+        let mut account = CoreAccount::<H>::from_bytes(data)?;
+        edit(&mut account)?;
+        account.to_bytes()
+    })
+    .await
+}
+
+/// Sets the owner of a core asset. Keys are assumed to be valid.
+/// `snapshot` is where to save the asset before changing it, see `WriteSet::apply`
+pub async fn rob_core_nft<S: AccountStore>(
+    store: &S,
+    nft_key: &str,
+    new_owner: &str,
+    plugin_changes: &PluginChanges,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let new_owner = Pubkey::from_str(new_owner)?;
+    edit_core_account(store, nft_key, rent, snapshot, |asset: &mut CoreAsset| {
+        asset.header.owner = new_owner;
+        plugin_changes.apply(asset)
+    })
+    .await
+}
+
+/// Sets the update authority of a core collection
+pub async fn rob_core_collection<S: AccountStore>(
+    store: &S,
+    collection_key: &str,
+    new_authority: &str,
+    plugin_changes: &PluginChanges,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let new_authority = Pubkey::from_str(new_authority)?;
+    edit_core_account(
+        store,
+        collection_key,
+        rent,
+        snapshot,
+        |collection: &mut CoreCollection| {
+            collection.header.update_authority = new_authority;
+            plugin_changes.apply(collection)
+        },
+    )
+    .await
+}
+
+/// Unfreezes a core asset without changing its owner or other plugins
+pub async fn unfreeze_core_nft<S: AccountStore>(
+    store: &S,
+    nft_key: &str,
+    revoke_freeze_delegate: bool,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    edit_core_account(store, nft_key, rent, snapshot, |asset: &mut CoreAsset| {
+        if !asset.unfreeze(revoke_freeze_delegate) {
+            anyhow::bail!("{} has no freeze plugin", nft_key);
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Moves a pNFT from `old_owner` to `new_owner`, closing the old ATA and token record and creating new ones
+pub async fn rob_pnft<S: AccountStore>(
+    store: &S,
+    mint: &str,
+    old_owner: &str,
+    new_owner: &str,
+    rent: RentMode,
+    snapshot: Option<&Path>,
+) -> Result<()> {
+    let mint_addr = Address::from_str(mint)?;
+    let mint_key = Pubkey::new_from_array(mint_addr.to_bytes());
+
+    let old_owner_addr = Address::from_str(old_owner)?;
+    let new_owner_addr = Address::from_str(new_owner)?;
+
+    let old_ata_addr = get_associated_token_address(&old_owner_addr, &mint_addr);
+    let new_ata_addr = get_associated_token_address(&new_owner_addr, &mint_addr);
+    let old_ata_key = Pubkey::new_from_array(old_ata_addr.to_bytes());
+    let new_ata_key = Pubkey::new_from_array(new_ata_addr.to_bytes());
+
+    let old_tra_key = TokenRecord::find_pda(&mint_key, &old_ata_key).0;
+    let new_tra_key = TokenRecord::find_pda(&mint_key, &new_ata_key).0;
+
+    let new_tra_pda = Pubkey::find_program_address(
+        &[
+            b"metadata",
+            mpl_token_metadata::ID.as_array(),
+            mint_key.as_array(),
+            b"token_record",
+            new_ata_key.as_array(),
+        ],
+        &mpl_token_metadata::ID,
+    );
+
+    let [old_ata_account, old_tra_account]: [_; 2] = store
+        .get_many(&[old_ata_addr.to_string(), old_tra_key.to_string()])
+        .await?
+        .try_into()
+        .expect("get_many returns one entry per key");
+    let old_ata_account = old_ata_account.ok_or_else(|| anyhow!("Old ATA does not exist!"))?;
+    let old_tra_account = old_tra_account.ok_or_else(|| anyhow!("Old TRA does not exist!"))?;
+
+    info!("Old ATA: {}", old_ata_key);
+    info!("New ATA: {}", new_ata_key);
+    info!("Old TRA: {}", old_tra_key);
+    info!("New TRA: {}", new_tra_key);
+
+    // Everything is cloned except the `owner` and `delegate` field of the ATA
+    // remove delegation from the ATA and set the owner to the new owner
+    let mut ata_info = deser_ata(&old_ata_account.data)?;
+    ata_info.owner = new_owner_addr;
+    ata_info.delegate = COption::None;
+
+    // completely unfreeze the pNFT (the ATA remains frozen), and remove delegation
+    let mut tra_info = deser_token_record(&old_tra_account.data)?;
+    tra_info.state = TokenState::Unlocked;
+    tra_info.bump = new_tra_pda.1;
+    tra_info.rule_set_revision = None;
+    tra_info.delegate = None;
+    tra_info.delegate_role = None;
+    tra_info.locked_transfer = None;
+
+    debug!("New token record: {:#?}", tra_info);
+    let ata_bytes = ser_ata(&ata_info)?;
+    let tra_bytes = ser_token_record(&tra_info)?;
+
+    // Nothing is written until everything is serialized, and a failed write restores the old accounts
+    let mut write_set = WriteSet::new();
+    write_set.close(old_ata_key);
+    write_set.close(old_tra_key);
+    write_set.set(
+        new_ata_key,
+        set_data(&new_ata_key.to_string(), &old_ata_account, &ata_bytes, rent)?,
+    );
+    write_set.set(
+        new_tra_key,
+        set_data(&new_tra_key.to_string(), &old_tra_account, &tra_bytes, rent)?,
+    );
+
+    info!("Closing old accounts and setting new ATA and TRA");
+    write_set.apply(store, snapshot).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_core::{
+        accounts::BaseCollectionV1,
+        types::{
+            Attributes, FreezeDelegate, Key, PermanentFreezeDelegate, Plugin, TransferDelegate,
+        },
+    };
+    use spl_token::state::{Account, AccountState};
+
+    use super::*;
+    use crate::{
+        mpl::*,
+        rent::minimum_balance,
+        test_support::{FakeAccount, FakeSurfnet, core_asset},
+    };
+
+    fn frozen_asset(owner: Pubkey) -> CoreAsset {
+        CoreAsset {
+            has_registry: true,
+            plugins: vec![PluginEntry {
+                authority: BasePluginAuthority::Owner,
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: true }),
+            }],
+            ..core_asset(owner)
+        }
+    }
+
+    #[tokio::test]
+    async fn rob_core_nft_keeps_plugins() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let new_owner = Pubkey::new_from_array([3; 32]);
+        let original = frozen_asset(Pubkey::new_from_array([2; 32]));
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        rob_core_nft(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            &new_owner.to_string(),
+            &PluginChanges::default(),
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        assert_eq!(robbed.header.owner, new_owner);
+        assert_eq!(robbed.plugins, original.plugins);
+    }
+
+    #[tokio::test]
+    async fn rob_core_nft_can_remove_plugins() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let new_owner = Pubkey::new_from_array([3; 32]);
+        let original = frozen_asset(Pubkey::new_from_array([2; 32]));
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        rob_core_nft(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            &new_owner.to_string(),
+            &PluginChanges {
+                remove_all: true,
+                ..Default::default()
+            },
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed = surfnet.account(asset).unwrap();
+        assert_eq!(
+            robbed.data,
+            ser_asset_header(&core_asset(new_owner).header).unwrap()
+        );
+        // the rent for the removed bytes is refunded
+        assert_eq!(robbed.lamports, minimum_balance(robbed.data.len()));
+    }
+
+    #[tokio::test]
+    async fn rob_core_nft_can_remove_one_plugin() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let new_owner = Pubkey::new_from_array([3; 32]);
+        let mut original = frozen_asset(Pubkey::new_from_array([2; 32]));
+        original.plugins.push(PluginEntry {
+            authority: BasePluginAuthority::UpdateAuthority,
+            plugin: Plugin::Attributes(Attributes {
+                attribute_list: Vec::new(),
+            }),
+        });
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        let remove_freeze = PluginChanges {
+            remove: vec![PluginType::FreezeDelegate],
+            ..Default::default()
+        };
+        rob_core_nft(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            &new_owner.to_string(),
+            &remove_freeze,
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        assert_eq!(robbed.plugins, original.plugins[1..]);
+
+        // it's gone now
+        assert!(
+            rob_core_nft(
+                &surfnet.rpc(),
+                &asset.to_string(),
+                &new_owner.to_string(),
+                &remove_freeze,
+                RentMode::Adjust,
+                None,
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn unfreeze_core_nft_keeps_the_owner() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let owner = Pubkey::new_from_array([2; 32]);
+        let mut original = frozen_asset(owner);
+        original.plugins[0].authority = BasePluginAuthority::Address {
+            address: Pubkey::new_from_array([4; 32]),
+        };
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        unfreeze_core_nft(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            true,
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let thawed = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        assert_eq!(thawed.header.owner, owner);
+        assert_eq!(
+            thawed.plugins,
+            vec![PluginEntry {
+                authority: BasePluginAuthority::Owner,
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: false }),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn rob_core_nft_resets_owner_managed_authorities() {
+        let asset = Pubkey::new_from_array([1; 32]);
+        let new_owner = Pubkey::new_from_array([3; 32]);
+        let delegate = BasePluginAuthority::Address {
+            address: Pubkey::new_from_array([4; 32]),
+        };
+        let mut original = frozen_asset(Pubkey::new_from_array([2; 32]));
+        original.plugins[0].authority = delegate.clone();
+        original.plugins.push(PluginEntry {
+            authority: delegate.clone(),
+            plugin: Plugin::TransferDelegate(TransferDelegate {}),
+        });
+        original.plugins.push(PluginEntry {
+            authority: delegate.clone(),
+            plugin: Plugin::Attributes(Attributes {
+                attribute_list: Vec::new(),
+            }),
+        });
+        let surfnet = FakeSurfnet::with_core_account(asset, &original).await;
+
+        rob_core_nft(
+            &surfnet.rpc(),
+            &asset.to_string(),
+            &new_owner.to_string(),
+            &PluginChanges {
+                reset_owner_managed: true,
+                authorities: vec![(PluginType::TransferDelegate, None)],
+                ..Default::default()
+            },
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed = CoreAsset::from_bytes(&surfnet.account(asset).unwrap().data).unwrap();
+        let authorities: Vec<_> = robbed
+            .plugins
+            .into_iter()
+            .map(|entry| entry.authority)
+            .collect();
+        // the transfer delegate is kept on purpose and attributes aren't owner managed
+        assert_eq!(
+            authorities,
+            vec![BasePluginAuthority::Owner, delegate.clone(), delegate]
+        );
+    }
+
+    #[test]
+    fn explicit_authorities_go_last() {
+        let delegate = BasePluginAuthority::Address {
+            address: Pubkey::new_from_array([4; 32]),
+        };
+        let mut asset = frozen_asset(Pubkey::new_from_array([2; 32]));
+        asset.plugins[0].authority = delegate.clone();
+        let changes = |authority| PluginChanges {
+            unfreeze: true,
+            revoke_freeze_delegate: true,
+            reset_owner_managed: true,
+            authorities: vec![(PluginType::FreezeDelegate, authority)],
+            ..Default::default()
+        };
+
+        let chosen = BasePluginAuthority::Address {
+            address: Pubkey::new_from_array([5; 32]),
+        };
+        let mut robbed = asset.clone();
+        changes(Some(chosen.clone())).apply(&mut robbed).unwrap();
+        assert_eq!(robbed.plugins[0].authority, chosen);
+        assert_eq!(
+            robbed.plugins[0].plugin,
+            Plugin::FreezeDelegate(FreezeDelegate { frozen: false })
+        );
+
+        // keep means the authority from before the rob
+        let mut robbed = asset.clone();
+        changes(None).apply(&mut robbed).unwrap();
+        assert_eq!(robbed.plugins[0].authority, delegate);
+
+        let mut twice = changes(None);
+        twice
+            .authorities
+            .push((PluginType::FreezeDelegate, Some(chosen)));
+        assert!(twice.apply(&mut asset).is_err());
+    }
+
+    #[tokio::test]
+    async fn rob_core_collection_sets_update_authority() {
+        let surfnet = FakeSurfnet::start().await;
+        let collection = Pubkey::new_from_array([1; 32]);
+        let new_authority = Pubkey::new_from_array([3; 32]);
+        let original = ser_collection_header(&BaseCollectionV1 {
+            key: Key::CollectionV1,
+            update_authority: Pubkey::new_from_array([2; 32]),
+            name: "Collection".into(),
+            uri: "https://example.com/collection.json".into(),
+            num_minted: 10,
+            current_size: 10,
+        })
+        .unwrap();
+        surfnet.set_account(collection, FakeAccount::new(mpl_core::ID, original));
+
+        rob_core_collection(
+            &surfnet.rpc(),
+            &collection.to_string(),
+            &new_authority.to_string(),
+            &PluginChanges::default(),
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed = deser_collection_header(&surfnet.account(collection).unwrap().data).unwrap();
+        assert_eq!(robbed.update_authority, new_authority);
+        assert_eq!(robbed.num_minted, 10);
+    }
+
+    #[tokio::test]
+    async fn rob_core_collection_can_remove_one_plugin() {
+        let collection = Pubkey::new_from_array([1; 32]);
+        let new_authority = Pubkey::new_from_array([3; 32]);
+        let original = CoreCollection {
+            header: BaseCollectionV1 {
+                key: Key::CollectionV1,
+                update_authority: Pubkey::new_from_array([2; 32]),
+                name: "Collection".into(),
+                uri: "https://example.com/collection.json".into(),
+                num_minted: 10,
+                current_size: 10,
+            },
+            has_registry: true,
+            plugins: vec![
+                PluginEntry {
+                    authority: BasePluginAuthority::UpdateAuthority,
+                    plugin: Plugin::PermanentFreezeDelegate(PermanentFreezeDelegate {
+                        frozen: true,
+                    }),
+                },
+                PluginEntry {
+                    authority: BasePluginAuthority::UpdateAuthority,
+                    plugin: Plugin::Attributes(Attributes {
+                        attribute_list: Vec::new(),
+                    }),
+                },
+            ],
+            unknown_plugins: Vec::new(),
+            external_plugins: Vec::new(),
+        };
+        let surfnet = FakeSurfnet::with_core_account(collection, &original).await;
+
+        rob_core_collection(
+            &surfnet.rpc(),
+            &collection.to_string(),
+            &new_authority.to_string(),
+            &PluginChanges {
+                remove: vec![PluginType::PermanentFreezeDelegate],
+                ..Default::default()
+            },
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let robbed =
+            CoreCollection::from_bytes(&surfnet.account(collection).unwrap().data).unwrap();
+        assert_eq!(robbed.header.update_authority, new_authority);
+        assert_eq!(robbed.plugins, original.plugins[1..]);
+    }
+
+    #[tokio::test]
+    async fn rob_pnft_moves_ata_and_token_record() {
+        let surfnet = FakeSurfnet::start().await;
+        let mint = Address::new_from_array([1; 32]);
+        let old_owner = Address::new_from_array([2; 32]);
+        let new_owner = Address::new_from_array([3; 32]);
+        let mint_key = Pubkey::new_from_array(mint.to_bytes());
+
+        let old_ata = get_associated_token_address(&old_owner, &mint);
+        let new_ata = get_associated_token_address(&new_owner, &mint);
+        let old_tra =
+            TokenRecord::find_pda(&mint_key, &Pubkey::new_from_array(old_ata.to_bytes())).0;
+        let new_tra =
+            TokenRecord::find_pda(&mint_key, &Pubkey::new_from_array(new_ata.to_bytes())).0;
+
+        // staked: frozen ATA, locked and delegated
+        let ata = Account {
+            mint,
+            owner: old_owner,
+            amount: 1,
+            delegate: COption::Some(Address::new_from_array([4; 32])),
+            state: AccountState::Frozen,
+            is_native: COption::None,
+            delegated_amount: 1,
+            close_authority: COption::None,
+        };
+        let token_record = TokenRecord {
+            key: mpl_token_metadata::types::Key::TokenRecord,
+            bump: 255,
+            state: TokenState::Locked,
+            rule_set_revision: None,
+            delegate: None,
+            delegate_role: None,
+            locked_transfer: None,
+        };
+        surfnet.set_account(
+            old_ata,
+            FakeAccount::new(spl_token::ID, ser_ata(&ata).unwrap()),
+        );
+        surfnet.set_account(
+            old_tra,
+            FakeAccount::new(
+                mpl_token_metadata::ID,
+                ser_token_record(&token_record).unwrap(),
+            ),
+        );
+
+        rob_pnft(
+            &surfnet.rpc(),
+            &mint.to_string(),
+            &old_owner.to_string(),
+            &new_owner.to_string(),
+            RentMode::Adjust,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(surfnet.account(old_ata).is_none());
+        assert!(surfnet.account(old_tra).is_none());
+
+        let new_ata_info = deser_ata(&surfnet.account(new_ata).unwrap().data).unwrap();
+        assert_eq!(new_ata_info.owner, new_owner);
+        assert_eq!(new_ata_info.delegate, COption::None);
+        assert_eq!(new_ata_info.amount, 1);
+
+        let new_tra_info = deser_token_record(&surfnet.account(new_tra).unwrap().data).unwrap();
+        assert_eq!(new_tra_info.state, TokenState::Unlocked);
+    }
+}
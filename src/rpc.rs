@@ -1,29 +1,308 @@
-use anyhow::{Result, anyhow};
-use log::info;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
-#[derive(Deserialize, Debug)]
+use anyhow::Result;
+use log::{debug, info, warn};
+use reqwest::{
+    Client, StatusCode,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::utils::{b58_to_bytes, b64_to_bytes, bytes_to_b58, bytes_to_hex, zstd_to_bytes};
+
+/// JSON-RPC error code for a known method called with the wrong params
+pub const INVALID_PARAMS: i32 = -32602;
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+/// Max number of keys `getMultipleAccounts` accepts
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// The `error` member of a JSON-RPC response
+#[derive(Deserialize, Debug, Clone)]
 pub struct RpcError {
     pub code: i32,
     pub message: String,
+    /// Solana RPCs put things like simulation logs in here
+    pub data: Option<Value>,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RPC Error {}: {}", self.code, self.message)?;
+        if let Some(data) = &self.data {
+            write!(f, " ({})", data)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RpcClientError {
+    /// Could not talk to the RPC at all (connection refused, timeout, etc)
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The RPC answered with a non 2xx status
+    #[error("HTTP status {status}: {body}")]
+    HttpStatus { status: StatusCode, body: String },
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Rpc(RpcError),
+    #[error("Unexpected RPC response: {0}")]
+    UnexpectedResponse(String),
+}
+
+impl RpcClientError {
+    /// Errors worth retrying: timeouts, connection errors and 5xx
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RpcClientError::Http(err) => err.is_timeout() || err.is_connect(),
+            RpcClientError::HttpStatus { status, .. } => status.is_server_error(),
+            RpcClientError::Json(_)
+            | RpcClientError::Rpc(_)
+            | RpcClientError::UnexpectedResponse(_) => false,
+        }
+    }
+}
+
+/// Timeout for every request, and how to retry idempotent calls
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub timeout: Duration,
+    /// Retries after the first attempt, so the total number of attempts is `max_retries + 1`
+    pub max_retries: u32,
+    /// Doubled after each retry, up to `max_backoff`
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// Only reads are retried, writes could end up applied twice
+const IDEMPOTENT_METHODS: [&str; 8] = [
+    "getAccountInfo",
+    "getMultipleAccounts",
+    "getProgramAccounts",
+    "getGenesisHash",
+    "simulateTransaction",
+    "surfnet_getStreamedAccounts",
+    "surfnet_getLocalSignatures",
+    "surfnet_exportSnapshot",
+];
+
+fn is_idempotent(method: &str) -> bool {
+    IDEMPOTENT_METHODS.contains(&method)
+}
+
+#[derive(Serialize, Debug)]
+struct RpcRequest<'a, P> {
+    jsonrpc: &'static str,
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcBatchResponse {
+    id: Option<u64>,
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+/// Most methods wrap their result in `{ context, value }`
+#[derive(Deserialize, Debug)]
+pub struct WithContext<T> {
+    pub value: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountEncoding {
+    /// Only allowed for accounts smaller than 129 bytes
+    #[serde(rename = "base58")]
+    Base58,
+    #[default]
+    #[serde(rename = "base64")]
+    Base64,
+    /// Good for big accounts
+    #[serde(rename = "base64+zstd")]
+    Base64Zstd,
+    /// Only works for accounts the RPC knows how to parse (token accounts etc), otherwise the RPC falls back to base64
+    #[serde(rename = "jsonParsed")]
+    JsonParsed,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct DataSlice {
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Config object for `getAccountInfo` and friends
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAccountInfoConfig {
+    pub encoding: AccountEncoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commitment: Option<Commitment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_context_slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_slice: Option<DataSlice>,
+}
+
+/// Filters for `getProgramAccounts`
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcFilter {
+    DataSize(u64),
+    Memcmp(Memcmp),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Memcmp {
+    pub offset: usize,
+    /// Always base58, every RPC supports it
+    pub bytes: String,
+}
+
+impl RpcFilter {
+    /// Matches accounts that have `bytes` at `offset`
+    pub fn memcmp(offset: usize, bytes: &[u8]) -> Self {
+        RpcFilter::Memcmp(Memcmp {
+            offset,
+            bytes: bytes_to_b58(bytes),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GetProgramAccountsConfig {
+    #[serde(flatten)]
+    pub account_config: GetAccountInfoConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<RpcFilter>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UiKeyedAccount {
+    pubkey: String,
+    account: UiAccount,
+}
+
+/// `data` as it comes from the RPC, before decoding
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum UiAccountData {
+    /// [data, encoding]
+    Encoded(String, AccountEncoding),
+    /// Result of `jsonParsed`
+    Parsed(Value),
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
+struct UiAccount {
+    lamports: u64,
+    data: UiAccountData,
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+    space: Option<u64>,
+}
+
+/// Account with its data already decoded
+#[derive(Debug, Clone)]
 #[allow(unused)]
 pub struct GetAccountInfoResponse {
     pub lamports: u64,
-    /// Usually [data, encoding(like base64)]
-    pub data: Vec<String>,
+    /// Raw account bytes. Empty if the account was returned as `jsonParsed`
+    pub data: Vec<u8>,
+    /// Only present if `jsonParsed` was requested and the RPC managed to parse the account
+    pub parsed: Option<Value>,
     pub owner: String,
     pub executable: bool,
     pub rent_epoch: u64,
     pub space: u64,
 }
 
-#[derive(Serialize, Debug)]
+impl TryFrom<UiAccount> for GetAccountInfoResponse {
+    type Error = anyhow::Error;
+
+    fn try_from(account: UiAccount) -> Result<Self> {
+        let (data, parsed) = match account.data {
+            UiAccountData::Encoded(data, encoding) => {
+                let bytes = match encoding {
+                    AccountEncoding::Base58 => b58_to_bytes(&data)?,
+                    AccountEncoding::Base64 => b64_to_bytes(&data)?,
+                    AccountEncoding::Base64Zstd => zstd_to_bytes(&b64_to_bytes(&data)?)?,
+                    AccountEncoding::JsonParsed => {
+                        anyhow::bail!("jsonParsed is not a valid encoding for raw data")
+                    }
+                };
+                (bytes, None)
+            }
+            UiAccountData::Parsed(value) => (Vec::new(), Some(value)),
+        };
+
+        Ok(Self {
+            space: account.space.unwrap_or(data.len() as u64),
+            lamports: account.lamports,
+            data,
+            parsed,
+            owner: account.owner,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+        })
+    }
+}
+
+/// Decodes an account in the same format `getAccountInfo` returns, for places that get accounts from elsewhere (like websocket notifications)
+pub fn decode_ui_account(value: Value) -> Result<GetAccountInfoResponse> {
+    let account: UiAccount = serde_json::from_value(value)?;
+    account.try_into()
+}
+
+#[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SetAccountInfo {
     pub data: Option<String>,
@@ -33,121 +312,478 @@ pub struct SetAccountInfo {
     pub rent_epoch: u64,
 }
 
+impl SetAccountInfo {
+    /// Writing this deletes the account
+    pub fn closed() -> Self {
+        Self {
+            data: Some(String::new()),
+            executable: false,
+            lamports: 0,
+            owner: SYSTEM_PROGRAM_ID.into(),
+            rent_epoch: 0,
+        }
+    }
+}
+
+impl GetAccountInfoResponse {
+    /// Writes the account back exactly as it was read
+    pub fn to_set_account_info(&self) -> Result<SetAccountInfo> {
+        Ok(SetAccountInfo {
+            data: Some(bytes_to_hex(&self.data)?),
+            executable: self.executable,
+            lamports: self.lamports,
+            owner: self.owner.clone(),
+            rent_epoch: self.rent_epoch,
+        })
+    }
+}
+
 pub struct Rpc {
     pub client: Client,
     pub url: String,
+    pub retry_policy: RetryPolicy,
+    next_id: AtomicU64,
 }
 
 impl Rpc {
-    pub fn new(url: String) -> Self {
-        Self {
-            client: Client::new(),
-            url,
+    /// `headers` are sent on every request, e.g. auth tokens for a remote fork
+    pub fn new(
+        url: String,
+        headers: &HashMap<String, String>,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self> {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
         }
-    }
 
-    pub async fn get_account_info(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>> {
-        let json_body = format!(
-            r#"{{
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getAccountInfo",
-            "params": ["{}"]
-        }}"#,
-            pubkey
-        );
+        Ok(Self {
+            client: Client::builder()
+                .default_headers(header_map)
+                .timeout(retry_policy.timeout)
+                .build()?,
+            url,
+            retry_policy,
+            next_id: AtomicU64::new(1),
+        })
+    }
 
-        info!("Getting info from account {}", pubkey);
+    fn request<'a, P>(&self, method: &'a str, params: P) -> RpcRequest<'a, P> {
+        RpcRequest {
+            jsonrpc: "2.0",
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            method,
+            params,
+        }
+    }
 
+    /// POSTs the body and returns the raw response text
+    async fn post(&self, body: Vec<u8>) -> Result<String, RpcClientError> {
         let res = self
             .client
             .post(&self.url)
             .header("Content-Type", "application/json")
-            .body(json_body)
+            .body(body)
             .send()
             .await?;
 
+        let status = res.status();
         let text = res.text().await?;
 
-        // Parse the response as a generic JSON value
-        let v: Value = serde_json::from_str(&text)?;
+        if !status.is_success() {
+            return Err(RpcClientError::HttpStatus { status, body: text });
+        }
+
+        Ok(text)
+    }
+
+    /// One attempt at a request, returns the raw `result`
+    async fn call_once(&self, body: Vec<u8>) -> Result<Value, RpcClientError> {
+        let text = self.post(body).await?;
+
+        let response: RpcResponse = serde_json::from_str(&text)
+            .map_err(|_| RpcClientError::UnexpectedResponse(text.clone()))?;
 
-        // If it has an error, deserialize it and return it
-        if let Some(err) = v.get("error") {
-            let rpc_error: RpcError = serde_json::from_value(err.clone())?;
-            return Err(anyhow!(
-                "RPC Error {}: {}",
-                rpc_error.code,
-                rpc_error.message
-            ));
+        if let Some(err) = response.error {
+            return Err(RpcClientError::Rpc(err));
         }
 
-        if let Some(result) = v.get("result") {
-            // if it has a value, but the value is null, return None
-            if result.get("value").is_none() || result.get("value").unwrap().is_null() {
-                return Ok(None);
-            }
+        // a missing result is treated like null, so that methods returning nothing can use `()`
+        Ok(response.result.unwrap_or(Value::Null))
+    }
+
+    /// Sends a single JSON-RPC request and deserializes its `result`.
+    /// Idempotent methods are retried on transient errors according to `retry_policy`
+    pub async fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, RpcClientError> {
+        let request = self.request(method, params);
+        let body = serde_json::to_vec(&request)?;
 
-            // if the value has info, return it
-            let account_info: GetAccountInfoResponse =
-                serde_json::from_value(result.get("value").unwrap().clone())?;
-            return Ok(Some(account_info));
+        let max_retries = if is_idempotent(method) {
+            self.retry_policy.max_retries
+        } else {
+            0
+        };
+
+        let mut retry = 0;
+        loop {
+            debug!("Calling {} (id {})", method, request.id);
+
+            match self.call_once(body.clone()).await {
+                Ok(result) => return Ok(serde_json::from_value(result)?),
+                Err(err) if err.is_transient() && retry < max_retries => {
+                    let backoff = self.retry_policy.backoff(retry);
+                    retry += 1;
+                    warn!(
+                        "{} failed ({}), retry {}/{} in {:?}",
+                        method, err, retry, max_retries, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                Err(err) => return Err(err),
+            }
         }
+    }
+
+    /// Sends all calls as a single JSON-RPC batch. The outer error is for the batch as a whole
+    /// (an RPC without batch support answers with a single `RpcClientError::Rpc`), the inner ones are per call, in the same order as `calls`
+    pub async fn call_batch<P: Serialize>(
+        &self,
+        calls: &[(&str, P)],
+    ) -> Result<Vec<Result<Value, RpcError>>, RpcClientError> {
+        let requests: Vec<_> = calls
+            .iter()
+            .map(|(method, params)| self.request(method, params))
+            .collect();
+
+        debug!("Calling a batch of {} requests", requests.len());
+
+        let text = self.post(serde_json::to_vec(&requests)?).await?;
+
+        let responses: Vec<RpcBatchResponse> = match serde_json::from_str(&text) {
+            Ok(responses) => responses,
+            Err(_) => {
+                let response: RpcResponse = serde_json::from_str(&text)
+                    .map_err(|_| RpcClientError::UnexpectedResponse(text.clone()))?;
+                return Err(match response.error {
+                    Some(err) => RpcClientError::Rpc(err),
+                    None => RpcClientError::UnexpectedResponse(text),
+                });
+            }
+        };
 
-        // "result" not found in the response, but there was also no error
-        Err(anyhow!("Unexpected RPC response: {}", text))
+        // responses can come back in any order
+        let mut by_id: HashMap<u64, RpcBatchResponse> = responses
+            .into_iter()
+            .filter_map(|response| response.id.map(|id| (id, response)))
+            .collect();
+
+        requests
+            .iter()
+            .map(|request| {
+                let response = by_id.remove(&request.id).ok_or_else(|| {
+                    RpcClientError::UnexpectedResponse(format!(
+                        "no response for request {} in batch: {}",
+                        request.id, text
+                    ))
+                })?;
+                Ok(match response.error {
+                    Some(err) => Err(err),
+                    None => Ok(response.result.unwrap_or(Value::Null)),
+                })
+            })
+            .collect()
     }
 
-    pub async fn set_account_info(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()> {
-        let json_body = format!(
-            r#"{{
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "surfnet_setAccount",
-            "params": ["{pubkey}", {}]
-        }}"#,
-            serde_json::to_string(info)?
+    /// Gets the account as base64, see `get_account_info_with_config` for other encodings
+    pub async fn get_account_info(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>> {
+        self.get_account_info_with_config(pubkey, &GetAccountInfoConfig::default())
+            .await
+    }
+
+    pub async fn get_account_info_with_config(
+        &self,
+        pubkey: &str,
+        config: &GetAccountInfoConfig,
+    ) -> Result<Option<GetAccountInfoResponse>> {
+        info!(
+            "Getting info from account {} as {:?}",
+            pubkey, config.encoding
         );
 
-        info!("Setting info for account {}", pubkey);
+        let response: WithContext<Option<UiAccount>> =
+            self.call("getAccountInfo", (pubkey, config)).await?;
+        response.value.map(TryInto::try_into).transpose()
+    }
 
-        let res = self
-            .client
-            .post(&self.url)
-            .header("Content-Type", "application/json")
-            .body(json_body)
-            .send()
-            .await?;
+    /// Same as `get_multiple_accounts_with_config`, as base64
+    pub async fn get_multiple_accounts(
+        &self,
+        pubkeys: &[String],
+    ) -> Result<Vec<Option<GetAccountInfoResponse>>> {
+        self.get_multiple_accounts_with_config(pubkeys, &GetAccountInfoConfig::default())
+            .await
+    }
 
-        let text = res.text().await?;
+    /// Results are in the same order as `pubkeys`. Requests are split so no more than `MAX_MULTIPLE_ACCOUNTS` keys are sent at once
+    pub async fn get_multiple_accounts_with_config(
+        &self,
+        pubkeys: &[String],
+        config: &GetAccountInfoConfig,
+    ) -> Result<Vec<Option<GetAccountInfoResponse>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
 
-        // Parse the response as a generic JSON value
-        let v: Value = serde_json::from_str(&text)?;
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            info!(
+                "Getting info from {} accounts as {:?}",
+                chunk.len(),
+                config.encoding
+            );
+
+            let response: WithContext<Vec<Option<UiAccount>>> =
+                self.call("getMultipleAccounts", (chunk, config)).await?;
+
+            if response.value.len() != chunk.len() {
+                anyhow::bail!(
+                    "Asked for {} accounts but got {}",
+                    chunk.len(),
+                    response.value.len()
+                );
+            }
 
-        // If it has an error, deserialize it and return it
-        if let Some(err) = v.get("error") {
-            let rpc_error: RpcError = serde_json::from_value(err.clone())?;
-            return Err(anyhow!(
-                "RPC Error {}: {}",
-                rpc_error.code,
-                rpc_error.message
-            ));
+            for account in response.value {
+                accounts.push(account.map(TryInto::try_into).transpose()?);
+            }
         }
 
+        Ok(accounts)
+    }
+
+    /// Every account owned by `program_id` that matches all the filters, as (pubkey, account)
+    pub async fn get_program_accounts(
+        &self,
+        program_id: &str,
+        config: &GetProgramAccountsConfig,
+    ) -> Result<Vec<(String, GetAccountInfoResponse)>> {
+        info!(
+            "Getting accounts of program {} with {} filters",
+            program_id,
+            config.filters.len()
+        );
+
+        let accounts: Vec<UiKeyedAccount> = self
+            .call("getProgramAccounts", (program_id, config))
+            .await?;
+
+        accounts
+            .into_iter()
+            .map(|keyed| Ok((keyed.pubkey, keyed.account.try_into()?)))
+            .collect()
+    }
+
+    pub async fn set_account_info(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()> {
+        info!("Setting info for account {}", pubkey);
+
+        let _: Value = self.call("surfnet_setAccount", (pubkey, info)).await?;
         Ok(())
     }
 
+    pub async fn get_genesis_hash(&self) -> Result<String> {
+        info!("Getting genesis hash");
+
+        Ok(self.call("getGenesisHash", json!([])).await?)
+    }
+
+    /// Calls `method` without params and returns true only if the RPC answers with "invalid params",
+    /// which is the one answer that means it knows the method. Any other answer, success included, is false
+    pub async fn rejects_empty_params(&self, method: &str) -> Result<bool> {
+        info!("Checking if the RPC supports {}", method);
+
+        match self.call::<_, Value>(method, json!([])).await {
+            Ok(_) => Ok(false),
+            Err(RpcClientError::Rpc(err)) => Ok(err.code == INVALID_PARAMS),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub async fn close_account(&self, pubkey: &str) -> Result<()> {
-        self.set_account_info(
-            pubkey,
-            &SetAccountInfo {
-                data: Some(String::new()),
-                executable: false,
-                lamports: 0,
-                owner: "11111111111111111111111111111111".into(),
-                rent_epoch: 0,
-            },
-        )
-        .await
+        self.set_account_info(pubkey, &SetAccountInfo::closed())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_support::{FakeAccount, FakeSurfnet},
+        utils::bytes_to_b64,
+    };
+
+    /// Account in the shape `getAccountInfo` returns, `space` left out if `None`
+    fn ui_account(data: Value, space: Option<u64>) -> Value {
+        let mut account = json!({
+            "lamports": 42,
+            "data": data,
+            "owner": SYSTEM_PROGRAM_ID,
+            "executable": false,
+            "rentEpoch": 7,
+        });
+        if let Some(space) = space {
+            account["space"] = json!(space);
+        }
+        account
+    }
+
+    #[test]
+    fn decodes_every_encoding() {
+        let compressed = zstd::encode_all(&[1u8, 2, 3][..], 0).unwrap();
+        for data in [
+            json!(["Ldp", "base58"]),
+            json!(["AQID", "base64"]),
+            json!([bytes_to_b64(&compressed), "base64+zstd"]),
+        ] {
+            let account = decode_ui_account(ui_account(data.clone(), Some(3))).unwrap();
+            assert_eq!(account.data, vec![1, 2, 3], "{}", data);
+            assert!(account.parsed.is_none());
+            assert_eq!(account.lamports, 42);
+            assert_eq!(account.owner, SYSTEM_PROGRAM_ID);
+            assert_eq!(account.rent_epoch, 7);
+        }
+
+        let bad = ui_account(json!(["AQID", "jsonParsed"]), Some(3));
+        assert!(decode_ui_account(bad).is_err());
+    }
+
+    #[test]
+    fn json_parsed_has_no_raw_data() {
+        let parsed = json!({
+            "program": "spl-token",
+            "parsed": { "type": "account", "info": { "tokenAmount": { "amount": "1" } } },
+            "space": 165,
+        });
+        let account = decode_ui_account(ui_account(parsed.clone(), Some(165))).unwrap();
+
+        assert!(account.data.is_empty());
+        assert_eq!(account.parsed, Some(parsed));
+        assert_eq!(account.space, 165);
+    }
+
+    #[test]
+    fn space_defaults_to_the_data_length() {
+        let account = decode_ui_account(ui_account(json!(["AQID", "base64"]), None)).unwrap();
+        assert_eq!(account.space, 3);
+
+        let account = decode_ui_account(ui_account(json!(["AQID", "base64"]), Some(10))).unwrap();
+        assert_eq!(account.space, 10);
+    }
+
+    #[test]
+    fn only_reads_are_retried() {
+        assert!(is_idempotent("getAccountInfo"));
+        assert!(is_idempotent("surfnet_getStreamedAccounts"));
+        assert!(!is_idempotent("surfnet_setAccount"));
+        assert!(!is_idempotent("sendTransaction"));
+    }
+
+    fn fast_retries(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(200),
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc_with(fast_retries(3));
+
+        surfnet.fail_requests_with(500, 2);
+        assert!(rpc.get_account_info("missing").await.unwrap().is_none());
+        assert_eq!(surfnet.requests(), 3);
+
+        // gives up after max_retries
+        surfnet.fail_requests_with(503, 10);
+        assert!(rpc.get_account_info("missing").await.is_err());
+        assert_eq!(surfnet.requests(), 3 + 4);
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_retried() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc_with(fast_retries(3));
+
+        surfnet.stall_requests(1);
+        assert!(rpc.get_account_info("missing").await.unwrap().is_none());
+        assert_eq!(surfnet.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn other_errors_and_writes_are_not_retried() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc_with(fast_retries(3));
+
+        // a retry would succeed, so any of these going through means it was retried
+        surfnet.fail_requests_with(429, 1);
+        assert!(rpc.get_account_info("missing").await.is_err());
+        assert_eq!(surfnet.requests(), 1);
+
+        let err = rpc
+            .call::<_, Value>("getAccountInfo", json!([]))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RpcClientError::Rpc(RpcError {
+                code: INVALID_PARAMS,
+                ..
+            })
+        ));
+        assert_eq!(surfnet.requests(), 2);
+
+        surfnet.fail_requests_with(500, 1);
+        let write = rpc
+            .call::<_, Value>(
+                "surfnet_setAccount",
+                json!(["key", SetAccountInfo::closed()]),
+            )
+            .await;
+        assert!(write.is_err());
+        assert_eq!(surfnet.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn many_accounts_are_fetched_in_chunks_in_order() {
+        let surfnet = FakeSurfnet::start().await;
+        let pubkeys: Vec<String> = (0..250).map(|i| format!("key{}", i)).collect();
+        // every third account is missing
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            if i % 3 != 0 {
+                surfnet.set_account(pubkey, FakeAccount::new(SYSTEM_PROGRAM_ID, vec![i as u8]));
+            }
+        }
+
+        let accounts = surfnet.rpc().get_multiple_accounts(&pubkeys).await.unwrap();
+
+        assert_eq!(surfnet.requests(), 3);
+        assert_eq!(accounts.len(), pubkeys.len());
+        for (i, account) in accounts.iter().enumerate() {
+            match account {
+                Some(account) => assert_eq!(account.data, vec![i as u8]),
+                None => assert_eq!(i % 3, 0),
+            }
+        }
+        assert_eq!(
+            accounts.iter().filter(|account| account.is_some()).count(),
+            166
+        );
     }
 }
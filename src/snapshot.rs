@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rpc::{GetAccountInfoResponse, SetAccountInfo},
+    utils::{b64_to_bytes, bytes_to_b64, bytes_to_hex, check_key_valid},
+    write_set::{PreImage, WriteSet},
+};
+
+/// Pre-images of every account a command touched, written before the command changes anything
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Snapshot {
+    pub accounts: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotEntry {
+    pub address: String,
+    /// `None` if the account did not exist, restoring closes it
+    pub account: Option<SnapshotAccount>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotAccount {
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    /// base64
+    pub data: String,
+}
+
+impl From<&GetAccountInfoResponse> for SnapshotAccount {
+    fn from(account: &GetAccountInfoResponse) -> Self {
+        Self {
+            lamports: account.lamports,
+            owner: account.owner.clone(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: bytes_to_b64(&account.data),
+        }
+    }
+}
+
+impl SnapshotAccount {
+    pub fn to_set_account_info(&self) -> Result<SetAccountInfo> {
+        Ok(SetAccountInfo {
+            data: Some(bytes_to_hex(&b64_to_bytes(&self.data)?)?),
+            executable: self.executable,
+            lamports: self.lamports,
+            owner: self.owner.clone(),
+            rent_epoch: self.rent_epoch,
+        })
+    }
+}
+
+impl Snapshot {
+    pub fn from_pre_images(pre_images: &[PreImage]) -> Self {
+        Self {
+            accounts: pre_images
+                .iter()
+                .map(|pre_image| SnapshotEntry {
+                    address: pre_image.pubkey.clone(),
+                    account: pre_image.account.as_ref().map(SnapshotAccount::from),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes that put every account back, recreating closed accounts and closing accounts that did not exist
+    pub fn restore_write_set(&self) -> Result<WriteSet> {
+        let mut write_set = WriteSet::new();
+        for entry in &self.accounts {
+            // the address ends up in a file name with --accounts-dir
+            check_key_valid(&entry.address)?;
+            match &entry.account {
+                Some(account) => write_set.set(&entry.address, account.to_set_account_info()?),
+                None => write_set.close(&entry.address),
+            }
+        }
+        Ok(write_set)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        info!(
+            "Saving snapshot of {} accounts to {}",
+            self.accounts.len(),
+            path.display()
+        );
+
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write snapshot {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read snapshot {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Could not parse snapshot {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::{
+        rpc::SYSTEM_PROGRAM_ID,
+        store::{AccountStore, MemoryStore},
+        test_support::{TempDir, info},
+    };
+
+    #[tokio::test]
+    async fn restore_undoes_a_write_set() {
+        let dir = TempDir::new("snapshot");
+        std::fs::create_dir_all(dir.path()).unwrap();
+        let path = dir.path().join("snapshot.json");
+
+        let closed = Pubkey::new_from_array([1; 32]).to_string();
+        let created = Pubkey::new_from_array([2; 32]).to_string();
+        let store = MemoryStore::new();
+        store.set(&closed, &info(&[1, 2, 3])).await.unwrap();
+
+        let mut write_set = WriteSet::new();
+        write_set.close(&closed);
+        write_set.set(&created, info(&[4]));
+        write_set.apply(&store, Some(&path)).await.unwrap();
+        assert!(store.get(&closed).await.unwrap().is_none());
+
+        let snapshot = Snapshot::load(&path).unwrap();
+        snapshot
+            .restore_write_set()
+            .unwrap()
+            .apply(&store, None)
+            .await
+            .unwrap();
+
+        let restored = store.get(&closed).await.unwrap().unwrap();
+        assert_eq!(restored.data, vec![1, 2, 3]);
+        assert_eq!(restored.lamports, 42);
+        assert_eq!(restored.owner, SYSTEM_PROGRAM_ID);
+        assert!(store.get(&created).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn restore_rejects_bad_addresses() {
+        let snapshot = Snapshot {
+            accounts: vec![SnapshotEntry {
+                address: "../outside".to_string(),
+                account: None,
+            }],
+        };
+        assert!(snapshot.restore_write_set().is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result, anyhow};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    rpc::*,
+    utils::{bytes_to_b64, hex_to_bytes},
+};
+
+/// Where accounts are read from and written to. The rob and print commands only go through this,
+/// so they work the same on surfpool, on a directory of dumped accounts or on an in-memory map
+// the futures don't need to be `Send`, everything here runs on one task, and harnesses like LiteSVM aren't `Sync` anyway
+#[allow(async_fn_in_trait)]
+pub trait AccountStore {
+    async fn get(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>>;
+
+    /// Results are in the same order as `pubkeys`
+    async fn get_many(&self, pubkeys: &[String]) -> Result<Vec<Option<GetAccountInfoResponse>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for pubkey in pubkeys {
+            accounts.push(self.get(pubkey).await?);
+        }
+        Ok(accounts)
+    }
+
+    async fn set(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()>;
+
+    /// Writes in order, stopping at the first failure
+    async fn set_many(&self, writes: &[(String, SetAccountInfo)]) -> Result<()> {
+        for (pubkey, info) in writes {
+            self.set(pubkey, info).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self, pubkey: &str) -> Result<()> {
+        self.set(pubkey, &SetAccountInfo::closed()).await
+    }
+}
+
+/// What `old` looks like after writing `info`, `None` if it gets deleted.
+/// Same as surfpool, leaving out the data keeps the old data
+fn apply_set_info(
+    old: Option<GetAccountInfoResponse>,
+    info: &SetAccountInfo,
+) -> Result<Option<GetAccountInfoResponse>> {
+    if info.lamports == 0 {
+        return Ok(None);
+    }
+
+    let data = match &info.data {
+        Some(data) => hex_to_bytes(data)?,
+        None => old.map(|old| old.data).unwrap_or_default(),
+    };
+
+    Ok(Some(GetAccountInfoResponse {
+        lamports: info.lamports,
+        space: data.len() as u64,
+        data,
+        parsed: None,
+        owner: info.owner.clone(),
+        executable: info.executable,
+        rent_epoch: info.rent_epoch,
+    }))
+}
+
+impl AccountStore for Rpc {
+    async fn get(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>> {
+        self.get_account_info(pubkey).await
+    }
+
+    async fn get_many(&self, pubkeys: &[String]) -> Result<Vec<Option<GetAccountInfoResponse>>> {
+        self.get_multiple_accounts(pubkeys).await
+    }
+
+    async fn set(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()> {
+        self.set_account_info(pubkey, info).await
+    }
+
+    /// Sent as a single JSON-RPC batch, or one by one if the RPC does not support batches
+    async fn set_many(&self, writes: &[(String, SetAccountInfo)]) -> Result<()> {
+        let calls: Vec<_> = writes
+            .iter()
+            .map(|(pubkey, info)| ("surfnet_setAccount", (pubkey.as_str(), info)))
+            .collect();
+
+        match self.call_batch(&calls).await {
+            Ok(results) => {
+                for ((pubkey, _), result) in writes.iter().zip(results) {
+                    result.map_err(|err| anyhow!("Setting {} failed: {}", pubkey, err))?;
+                }
+                Ok(())
+            }
+            Err(RpcClientError::Rpc(err)) => {
+                warn!(
+                    "RPC refused the batch ({}), writing accounts one by one",
+                    err
+                );
+                for (pubkey, info) in writes {
+                    self.set_account_info(pubkey, info).await?;
+                }
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Accounts kept in a map, for embedding the commands in other test harnesses
+#[derive(Default)]
+pub struct MemoryStore {
+    accounts: Mutex<HashMap<String, GetAccountInfoResponse>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, pubkey: impl ToString, account: GetAccountInfoResponse) {
+        self.accounts
+            .lock()
+            .unwrap()
+            .insert(pubkey.to_string(), account);
+    }
+}
+
+impl AccountStore for MemoryStore {
+    async fn get(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>> {
+        Ok(self.accounts.lock().unwrap().get(pubkey).cloned())
+    }
+
+    async fn set(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()> {
+        let mut accounts = self.accounts.lock().unwrap();
+        match apply_set_info(accounts.get(pubkey).cloned(), info)? {
+            Some(account) => accounts.insert(pubkey.to_string(), account),
+            None => accounts.remove(pubkey),
+        };
+        Ok(())
+    }
+}
+
+/// One account, in the JSON layout `solana account --output json` and `solana-test-validator --account` use
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccountFile {
+    pub pubkey: String,
+    /// Same format as `getAccountInfo` returns
+    pub account: Value,
+}
+
+impl AccountFile {
+    pub fn new(pubkey: &str, account: &GetAccountInfoResponse) -> Self {
+        Self {
+            pubkey: pubkey.to_string(),
+            account: json!({
+                "lamports": account.lamports,
+                "data": [bytes_to_b64(&account.data), "base64"],
+                "owner": account.owner,
+                "executable": account.executable,
+                "rentEpoch": account.rent_epoch,
+                "space": account.data.len(),
+            }),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read account file {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Could not parse account file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write account file {}", path.display()))
+    }
+
+    pub fn decode(&self) -> Result<GetAccountInfoResponse> {
+        decode_ui_account(self.account.clone())
+    }
+}
+
+/// A directory with one `<pubkey>.json` account file per account. Closing an account deletes its file
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create {}", dir.display()))?;
+        info!("Using accounts in {}", dir.display());
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn path(&self, pubkey: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", pubkey))
+    }
+}
+
+impl AccountStore for DirStore {
+    async fn get(&self, pubkey: &str) -> Result<Option<GetAccountInfoResponse>> {
+        let path = self.path(pubkey);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(AccountFile::load(&path)?.decode()?))
+    }
+
+    async fn set(&self, pubkey: &str, info: &SetAccountInfo) -> Result<()> {
+        let path = self.path(pubkey);
+        let old = self.get(pubkey).await?;
+        match apply_set_info(old, info)? {
+            Some(account) => AccountFile::new(pubkey, &account).save(&path),
+            None => {
+                if path.exists() {
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("Could not delete {}", path.display()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TempDir, info};
+
+    #[tokio::test]
+    async fn memory_store_set_get_close() {
+        let store = MemoryStore::new();
+
+        store.set("key", &info(&[1, 2, 3])).await.unwrap();
+        let account = store.get("key").await.unwrap().unwrap();
+        assert_eq!(account.data, vec![1, 2, 3]);
+        assert_eq!(account.lamports, 42);
+
+        store.close("key").await.unwrap();
+        assert!(store.get("key").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn dir_store_set_get_close() {
+        let dir = TempDir::new("dir_store");
+        let store = DirStore::new(dir.path()).unwrap();
+
+        store.set("key", &info(&[1, 2, 3])).await.unwrap();
+        let accounts = store
+            .get_many(&["key".to_string(), "missing".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(accounts[0].as_ref().unwrap().data, vec![1, 2, 3]);
+        assert!(accounts[1].is_none());
+
+        store.close("key").await.unwrap();
+        assert!(store.get("key").await.unwrap().is_none());
+    }
+}
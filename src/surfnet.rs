@@ -0,0 +1,483 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{ArgGroup, Subcommand};
+use log::info;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{rpc::Rpc, utils::check_key_valid};
+
+/// Fields of a token account to change with `surfnet_setTokenAccount`, `None` keeps the current value
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenAccountUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegate: Option<String>,
+    /// `initialized` or `frozen`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegated_amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_authority: Option<String>,
+}
+
+/// Exactly one of the fields should be set
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeTravelConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_epoch: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_slot: Option<u64>,
+    /// Unix timestamp in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SupplyUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circulating: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub non_circulating: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct IncludeOwnedAccounts {
+    include_owned_accounts: bool,
+}
+
+/// Wrappers for the surfpool cheatcodes. `surfnet_setAccount` lives with the other account methods in `rpc.rs`
+impl Rpc {
+    /// Creates or updates the ATA of `owner` for `mint`
+    pub async fn set_token_account(
+        &self,
+        owner: &str,
+        mint: &str,
+        update: &TokenAccountUpdate,
+        token_program: Option<&str>,
+    ) -> Result<()> {
+        info!("Setting token account of {} for mint {}", owner, mint);
+
+        let _: Value = self
+            .call(
+                "surfnet_setTokenAccount",
+                (owner, mint, update, token_program),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Copies the program (and its program data account) from `source` to `destination`
+    pub async fn clone_program_account(&self, source: &str, destination: &str) -> Result<()> {
+        info!("Cloning program {} into {}", source, destination);
+
+        let _: Value = self
+            .call("surfnet_cloneProgramAccount", (source, destination))
+            .await?;
+        Ok(())
+    }
+
+    /// `None` makes the program immutable
+    pub async fn set_program_authority(
+        &self,
+        program_id: &str,
+        new_authority: Option<&str>,
+    ) -> Result<()> {
+        info!(
+            "Setting authority of program {} to {:?}",
+            program_id, new_authority
+        );
+
+        let _: Value = self
+            .call("surfnet_setProgramAuthority", (program_id, new_authority))
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the local changes to the account, so it is fetched from the upstream RPC again
+    pub async fn reset_account(&self, pubkey: &str, include_owned_accounts: bool) -> Result<()> {
+        info!("Resetting account {}", pubkey);
+
+        let _: Value = self
+            .call(
+                "surfnet_resetAccount",
+                (
+                    pubkey,
+                    IncludeOwnedAccounts {
+                        include_owned_accounts,
+                    },
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Drops every local change
+    pub async fn reset_network(&self) -> Result<()> {
+        info!("Resetting the network");
+
+        let _: Value = self.call("surfnet_resetNetwork", json!([])).await?;
+        Ok(())
+    }
+
+    /// Returns the new epoch info
+    pub async fn time_travel(&self, config: &TimeTravelConfig) -> Result<Value> {
+        info!("Time travelling to {:?}", config);
+
+        Ok(self.call("surfnet_timeTravel", [config]).await?)
+    }
+
+    pub async fn pause_clock(&self) -> Result<()> {
+        info!("Pausing the clock");
+
+        let _: Value = self.call("surfnet_pauseClock", json!([])).await?;
+        Ok(())
+    }
+
+    pub async fn resume_clock(&self) -> Result<()> {
+        info!("Resuming the clock");
+
+        let _: Value = self.call("surfnet_resumeClock", json!([])).await?;
+        Ok(())
+    }
+
+    pub async fn set_supply(&self, update: &SupplyUpdate) -> Result<()> {
+        info!("Setting supply to {:?}", update);
+
+        let _: Value = self.call("surfnet_setSupply", [update]).await?;
+        Ok(())
+    }
+
+    /// Makes surfpool always fetch the account from upstream instead of keeping a local copy
+    pub async fn stream_account(&self, pubkey: &str, include_owned_accounts: bool) -> Result<()> {
+        info!("Streaming account {}", pubkey);
+
+        let _: Value = self
+            .call(
+                "surfnet_streamAccount",
+                (
+                    pubkey,
+                    IncludeOwnedAccounts {
+                        include_owned_accounts,
+                    },
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_streamed_accounts(&self) -> Result<Value> {
+        info!("Getting streamed accounts");
+
+        Ok(self.call("surfnet_getStreamedAccounts", json!([])).await?)
+    }
+
+    pub async fn get_local_signatures(&self, limit: Option<u64>) -> Result<Value> {
+        info!("Getting local signatures");
+
+        Ok(self.call("surfnet_getLocalSignatures", [limit]).await?)
+    }
+
+    /// Every account surfpool has locally, as a JSON map
+    pub async fn export_snapshot(&self) -> Result<Value> {
+        info!("Exporting snapshot");
+
+        Ok(self.call("surfnet_exportSnapshot", json!([])).await?)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SurfnetCommands {
+    #[command(about = "Create or update the token account of an owner for a mint")]
+    SetTokenAccount {
+        owner: String,
+        mint: String,
+        #[arg(long)]
+        amount: Option<u64>,
+        #[arg(long)]
+        delegate: Option<String>,
+        #[arg(long)]
+        delegated_amount: Option<u64>,
+        #[arg(long)]
+        close_authority: Option<String>,
+        /// `initialized` or `frozen`
+        #[arg(long)]
+        state: Option<String>,
+        /// Defaults to the token program, use this for token-2022
+        #[arg(long)]
+        token_program: Option<String>,
+    },
+    #[command(about = "Copy a program into another address")]
+    CloneProgram { source: String, destination: String },
+    #[command(about = "Set the upgrade authority of a program, none makes it immutable")]
+    SetProgramAuthority {
+        program_id: String,
+        /// `none`, or leaving it out, makes the program immutable
+        new_authority: Option<String>,
+    },
+    #[command(about = "Drop local changes to an account")]
+    ResetAccount {
+        key: String,
+        #[arg(long)]
+        include_owned: bool,
+    },
+    #[command(about = "Drop every local change")]
+    ResetNetwork,
+    #[command(about = "Move the clock to a slot, epoch or timestamp")]
+    #[command(group(ArgGroup::new("target").required(true).args(["slot", "epoch", "timestamp"])))]
+    TimeTravel {
+        #[arg(long)]
+        slot: Option<u64>,
+        #[arg(long)]
+        epoch: Option<u64>,
+        /// Unix timestamp in milliseconds
+        #[arg(long)]
+        timestamp: Option<u64>,
+    },
+    #[command(about = "Stop producing blocks")]
+    PauseClock,
+    #[command(about = "Start producing blocks again")]
+    ResumeClock,
+    #[command(about = "Override the values returned by getSupply")]
+    SetSupply {
+        #[arg(long)]
+        total: Option<u64>,
+        #[arg(long)]
+        circulating: Option<u64>,
+        #[arg(long)]
+        non_circulating: Option<u64>,
+    },
+    #[command(about = "Always fetch an account from upstream instead of caching it")]
+    StreamAccount {
+        key: String,
+        #[arg(long)]
+        include_owned: bool,
+    },
+    #[command(about = "List streamed accounts")]
+    StreamedAccounts,
+    #[command(about = "List signatures of transactions sent to the surfnet")]
+    LocalSignatures {
+        #[arg(long)]
+        limit: Option<u64>,
+    },
+    #[command(about = "Export every local account as JSON")]
+    ExportSnapshot {
+        /// Prints to stdout if not given
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+impl SurfnetCommands {
+    pub fn is_mutating(&self) -> bool {
+        !matches!(
+            self,
+            SurfnetCommands::StreamedAccounts
+                | SurfnetCommands::LocalSignatures { .. }
+                | SurfnetCommands::ExportSnapshot { .. }
+        )
+    }
+}
+
+pub async fn run_surfnet_command(rpc: &Rpc, command: SurfnetCommands) -> Result<()> {
+    match command {
+        SurfnetCommands::SetTokenAccount {
+            owner,
+            mint,
+            amount,
+            delegate,
+            delegated_amount,
+            close_authority,
+            state,
+            token_program,
+        } => {
+            check_key_valid(&owner)?;
+            check_key_valid(&mint)?;
+            for key in [&delegate, &close_authority, &token_program]
+                .into_iter()
+                .flatten()
+            {
+                check_key_valid(key)?;
+            }
+
+            let update = TokenAccountUpdate {
+                amount,
+                delegate,
+                state,
+                delegated_amount,
+                close_authority,
+            };
+            rpc.set_token_account(&owner, &mint, &update, token_program.as_deref())
+                .await?;
+        }
+        SurfnetCommands::CloneProgram {
+            source,
+            destination,
+        } => {
+            check_key_valid(&source)?;
+            check_key_valid(&destination)?;
+            rpc.clone_program_account(&source, &destination).await?;
+        }
+        SurfnetCommands::SetProgramAuthority {
+            program_id,
+            new_authority,
+        } => {
+            check_key_valid(&program_id)?;
+            let new_authority =
+                new_authority.filter(|authority| !authority.eq_ignore_ascii_case("none"));
+            if let Some(new_authority) = &new_authority {
+                check_key_valid(new_authority)?;
+            }
+            rpc.set_program_authority(&program_id, new_authority.as_deref())
+                .await?;
+        }
+        SurfnetCommands::ResetAccount { key, include_owned } => {
+            check_key_valid(&key)?;
+            rpc.reset_account(&key, include_owned).await?;
+        }
+        SurfnetCommands::ResetNetwork => {
+            rpc.reset_network().await?;
+        }
+        SurfnetCommands::TimeTravel {
+            slot,
+            epoch,
+            timestamp,
+        } => {
+            let epoch_info = rpc
+                .time_travel(&TimeTravelConfig {
+                    absolute_epoch: epoch,
+                    absolute_slot: slot,
+                    absolute_timestamp: timestamp,
+                })
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&epoch_info)?);
+        }
+        SurfnetCommands::PauseClock => {
+            rpc.pause_clock().await?;
+        }
+        SurfnetCommands::ResumeClock => {
+            rpc.resume_clock().await?;
+        }
+        SurfnetCommands::SetSupply {
+            total,
+            circulating,
+            non_circulating,
+        } => {
+            rpc.set_supply(&SupplyUpdate {
+                total,
+                circulating,
+                non_circulating,
+            })
+            .await?;
+        }
+        SurfnetCommands::StreamAccount { key, include_owned } => {
+            check_key_valid(&key)?;
+            rpc.stream_account(&key, include_owned).await?;
+        }
+        SurfnetCommands::StreamedAccounts => {
+            let accounts = rpc.get_streamed_accounts().await?;
+            println!("{}", serde_json::to_string_pretty(&accounts)?);
+        }
+        SurfnetCommands::LocalSignatures { limit } => {
+            let signatures = rpc.get_local_signatures(limit).await?;
+            println!("{}", serde_json::to_string_pretty(&signatures)?);
+        }
+        SurfnetCommands::ExportSnapshot { out } => {
+            let snapshot = serde_json::to_string_pretty(&rpc.export_snapshot().await?)?;
+            match out {
+                Some(path) => std::fs::write(path, snapshot)?,
+                None => println!("{}", snapshot),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_pubkey::Pubkey;
+
+    use super::*;
+    use crate::test_support::FakeSurfnet;
+
+    #[tokio::test]
+    async fn cheatcode_params() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc();
+
+        let update = TokenAccountUpdate {
+            amount: Some(5),
+            state: Some("frozen".into()),
+            delegated_amount: Some(1),
+            ..Default::default()
+        };
+        rpc.set_token_account("owner", "mint", &update, None)
+            .await
+            .unwrap();
+        rpc.time_travel(&TimeTravelConfig {
+            absolute_slot: Some(100),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            surfnet.calls(),
+            vec![
+                (
+                    "surfnet_setTokenAccount".to_string(),
+                    json!([
+                        "owner",
+                        "mint",
+                        { "amount": 5, "state": "frozen", "delegatedAmount": 1 },
+                        null
+                    ])
+                ),
+                (
+                    "surfnet_timeTravel".to_string(),
+                    json!([{ "absoluteSlot": 100 }])
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn none_makes_a_program_immutable() {
+        let surfnet = FakeSurfnet::start().await;
+        let program_id = Pubkey::new_from_array([1; 32]).to_string();
+
+        run_surfnet_command(
+            &surfnet.rpc(),
+            SurfnetCommands::SetProgramAuthority {
+                program_id: program_id.clone(),
+                new_authority: Some("none".into()),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            surfnet.calls(),
+            vec![(
+                "surfnet_setProgramAuthority".to_string(),
+                json!([program_id, null])
+            )]
+        );
+
+        let bad = SurfnetCommands::SetProgramAuthority {
+            program_id,
+            new_authority: Some("nobody".into()),
+        };
+        assert!(run_surfnet_command(&surfnet.rpc(), bad).await.is_err());
+    }
+}
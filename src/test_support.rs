@@ -0,0 +1,397 @@
+//! In-memory stand-in for surfpool, so commands can be tested without a network, and other test helpers
+
+// the library and the binary tests both include this and each only use part of it
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mpl_core::{
+    accounts::BaseAssetV1,
+    types::{Key, UpdateAuthority},
+};
+use serde_json::{Value, json};
+use solana_pubkey::Pubkey;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    core_account::{CoreAccount, CoreAsset, CoreHeader},
+    rpc::{MAX_MULTIPLE_ACCOUNTS, RetryPolicy, Rpc, SYSTEM_PROGRAM_ID, SetAccountInfo},
+    utils::bytes_to_b64,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FakeAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+}
+
+impl FakeAccount {
+    pub fn new(owner: impl ToString, data: Vec<u8>) -> Self {
+        Self {
+            lamports: 1_000_000,
+            data,
+            owner: owner.to_string(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<String, FakeAccount>,
+    /// `surfnet_setAccount` on these keys fails, to test rollbacks
+    failing_writes: HashSet<String>,
+    /// Error code for `surfnet_setAccount` without params, instead of "invalid params"
+    probe_error: Option<i32>,
+    /// Every HTTP request received, to count retries
+    requests: u32,
+    /// HTTP status to answer the next requests with and how many of them
+    http_errors: Option<(u16, u32)>,
+    /// How many of the next requests are only answered after `STALL`
+    stalls: u32,
+    /// Method and params of every call, batched ones included
+    calls: Vec<(String, Value)>,
+}
+
+/// Long enough for any client timeout used in the tests
+const STALL: Duration = Duration::from_secs(5);
+
+/// JSON-RPC server implementing `getAccountInfo`, `getMultipleAccounts`, `getGenesisHash` and `surfnet_setAccount`
+/// (batches included) over an account map, and accepting the other cheatcodes. Stops when dropped
+pub struct FakeSurfnet {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+}
+
+impl Drop for FakeSurfnet {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+impl FakeSurfnet {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state: Arc<Mutex<State>> = Arc::default();
+
+        let server_state = state.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, server_state.clone()));
+            }
+        });
+
+        Self { url, state, server }
+    }
+
+    pub fn rpc(&self) -> Rpc {
+        self.rpc_with(RetryPolicy::default())
+    }
+
+    pub fn rpc_with(&self, retry_policy: RetryPolicy) -> Rpc {
+        Rpc::new(self.url.clone(), &HashMap::new(), retry_policy).unwrap()
+    }
+
+    pub fn set_account(&self, pubkey: impl ToString, account: FakeAccount) {
+        self.state
+            .lock()
+            .unwrap()
+            .accounts
+            .insert(pubkey.to_string(), account);
+    }
+
+    pub fn account(&self, pubkey: impl ToString) -> Option<FakeAccount> {
+        self.state
+            .lock()
+            .unwrap()
+            .accounts
+            .get(&pubkey.to_string())
+            .cloned()
+    }
+
+    /// Makes the surfpool probe fail with `code`, like a provider that does not know the cheatcodes would
+    pub fn answer_probe_with(&self, code: i32) {
+        self.state.lock().unwrap().probe_error = Some(code);
+    }
+
+    /// Answers the next `times` requests with HTTP `status` without looking at them
+    pub fn fail_requests_with(&self, status: u16, times: u32) {
+        self.state.lock().unwrap().http_errors = Some((status, times));
+    }
+
+    /// Holds the next `times` requests for longer than any client waits
+    pub fn stall_requests(&self, times: u32) {
+        self.state.lock().unwrap().stalls = times;
+    }
+
+    pub fn calls(&self) -> Vec<(String, Value)> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// HTTP requests received so far, batches count once
+    pub fn requests(&self) -> u32 {
+        self.state.lock().unwrap().requests
+    }
+
+    pub fn fail_writes_to(&self, pubkey: impl ToString) {
+        self.state
+            .lock()
+            .unwrap()
+            .failing_writes
+            .insert(pubkey.to_string());
+    }
+}
+
+/// Minimal HTTP/1.1 with keep-alive, only what reqwest sends
+async fn serve_connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+
+    loop {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+
+        let mut body = vec![0u8; content_length];
+        if reader.read_exact(&mut body).await.is_err() {
+            return;
+        }
+
+        let (http_error, stall) = {
+            let mut state = state.lock().unwrap();
+            state.requests += 1;
+            let http_error = match &mut state.http_errors {
+                Some((status, times)) if *times > 0 => {
+                    *times -= 1;
+                    Some(*status)
+                }
+                _ => None,
+            };
+            let stall = state.stalls > 0;
+            if stall {
+                state.stalls -= 1;
+            }
+            (http_error, stall)
+        };
+        if stall {
+            tokio::time::sleep(STALL).await;
+        }
+
+        let (status, response) = match http_error {
+            Some(status) => (status, "Failed on purpose".to_string()),
+            None => {
+                let response = match serde_json::from_slice::<Value>(&body) {
+                    Ok(request) => handle_request(&state, request),
+                    Err(_) => rpc_error(Value::Null, -32700, "Parse error"),
+                };
+                (200, response.to_string())
+            }
+        };
+
+        let http_response = format!(
+            "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        );
+        if write.write_all(http_response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn rpc_error(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn handle_request(state: &Mutex<State>, request: Value) -> Value {
+    if let Value::Array(requests) = request {
+        return Value::Array(
+            requests
+                .into_iter()
+                .map(|request| handle_request(state, request))
+                .collect(),
+        );
+    }
+
+    let id = request["id"].clone();
+    let params = &request["params"];
+    let method = request["method"].as_str().unwrap_or_default();
+    let mut state = state.lock().unwrap();
+    state.calls.push((method.to_string(), params.clone()));
+
+    let result = match method {
+        "getAccountInfo" => {
+            let Some(pubkey) = params[0].as_str() else {
+                return rpc_error(id, -32602, "Invalid params");
+            };
+            json!({
+                "context": { "slot": 1 },
+                "value": encode_account(state.accounts.get(pubkey)),
+            })
+        }
+        "getMultipleAccounts" => {
+            let Some(pubkeys) = params[0].as_array() else {
+                return rpc_error(id, -32602, "Invalid params");
+            };
+            // same limit as a real RPC
+            if pubkeys.len() > MAX_MULTIPLE_ACCOUNTS {
+                return rpc_error(id, -32602, "Too many inputs provided");
+            }
+            let accounts: Vec<Value> = pubkeys
+                .iter()
+                .map(|pubkey| encode_account(pubkey.as_str().and_then(|p| state.accounts.get(p))))
+                .collect();
+            json!({ "context": { "slot": 1 }, "value": accounts })
+        }
+        "getGenesisHash" => json!("FakeSurfnetGenesisHash11111111111111111111111"),
+        "surfnet_setAccount" => {
+            let (Some(pubkey), Some(update)) = (params[0].as_str(), params[1].as_object()) else {
+                return rpc_error(id, state.probe_error.unwrap_or(-32602), "Invalid params");
+            };
+            if state.failing_writes.contains(pubkey) {
+                return rpc_error(id, -32603, "Write failed on purpose");
+            }
+
+            let old_data = state
+                .accounts
+                .get(pubkey)
+                .map(|account| account.data.clone())
+                .unwrap_or_default();
+            let data = match update.get("data").and_then(Value::as_str) {
+                Some(hex_data) => hex::decode(hex_data).unwrap(),
+                None => old_data,
+            };
+            let account = FakeAccount {
+                lamports: update["lamports"].as_u64().unwrap_or_default(),
+                data,
+                owner: update["owner"].as_str().unwrap_or_default().to_string(),
+                executable: update["executable"].as_bool().unwrap_or_default(),
+                rent_epoch: update["rentEpoch"].as_u64().unwrap_or_default(),
+            };
+
+            // same as a real cluster, an account without lamports does not exist
+            if account.lamports == 0 {
+                state.accounts.remove(pubkey);
+            } else {
+                state.accounts.insert(pubkey.to_string(), account);
+            }
+            json!({ "context": { "slot": 1 }, "value": null })
+        }
+        // the other cheatcodes only change things inside surfpool, `calls` shows what was sent
+        method if method.starts_with("surfnet_") => Value::Null,
+        _ => return rpc_error(id, -32601, "Method not found"),
+    };
+
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn encode_account(account: Option<&FakeAccount>) -> Value {
+    match account {
+        Some(account) => json!({
+            "lamports": account.lamports,
+            "data": [bytes_to_b64(&account.data), "base64"],
+            "owner": account.owner,
+            "executable": account.executable,
+            "rentEpoch": account.rent_epoch,
+            "space": account.data.len(),
+        }),
+        None => Value::Null,
+    }
+}
+
+/// System owned account with `data` and 42 lamports
+pub fn info(data: &[u8]) -> SetAccountInfo {
+    SetAccountInfo {
+        data: Some(hex::encode(data)),
+        executable: false,
+        lamports: 42,
+        owner: SYSTEM_PROGRAM_ID.into(),
+        rent_epoch: 0,
+    }
+}
+
+/// Scratch directory in the system temp dir, deleted on drop.
+/// `name` keeps tests that run at the same time apart
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nft_robber_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Asset owned by `owner` without plugins, tests add the ones they need
+pub fn core_asset(owner: Pubkey) -> CoreAsset {
+    CoreAsset {
+        header: BaseAssetV1 {
+            key: Key::AssetV1,
+            owner,
+            update_authority: UpdateAuthority::None,
+            name: "Asset".into(),
+            uri: "https://example.com/asset.json".into(),
+            seq: None,
+        },
+        has_registry: false,
+        plugins: Vec::new(),
+        unknown_plugins: Vec::new(),
+        external_plugins: Vec::new(),
+    }
+}
+
+impl FakeSurfnet {
+    /// Starts a surfnet with `account` at `pubkey`, owned by mpl-core
+    pub async fn with_core_account<H: CoreHeader>(
+        pubkey: Pubkey,
+        account: &CoreAccount<H>,
+    ) -> Self {
+        let surfnet = Self::start().await;
+        surfnet.set_account(
+            pubkey,
+            FakeAccount::new(mpl_core::ID, account.to_bytes().unwrap()),
+        );
+        surfnet
+    }
+}
@@ -1,10 +1,42 @@
+use std::str::FromStr;
+
 use anyhow::Result;
 use base64::{Engine, engine::general_purpose};
+use solana_pubkey::Pubkey;
 
 pub fn b64_to_bytes(b64: &str) -> Result<Vec<u8>> {
     Ok(general_purpose::STANDARD.decode(b64)?)
 }
 
+pub fn bytes_to_b64(bytes: &[u8]) -> String {
+    general_purpose::STANDARD.encode(bytes)
+}
+
+pub fn b58_to_bytes(b58: &str) -> Result<Vec<u8>> {
+    Ok(bs58::decode(b58).into_vec()?)
+}
+
+pub fn bytes_to_b58(bytes: &[u8]) -> String {
+    bs58::encode(bytes).into_string()
+}
+
+pub fn zstd_to_bytes(compressed: &[u8]) -> Result<Vec<u8>> {
+    Ok(zstd::decode_all(compressed)?)
+}
+
 pub fn bytes_to_hex(bytes: &[u8]) -> Result<String> {
-    Ok(format!("{}", hex::encode(bytes)))
+    Ok(hex::encode(bytes))
+}
+
+pub fn hex_to_bytes(hex_string: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(hex_string)?)
+}
+
+// cursed
+pub fn check_key_valid(key: &str) -> Result<()> {
+    if Pubkey::from_str(key).is_ok() {
+        Ok(())
+    } else {
+        anyhow::bail!("{} is not a valid key", key);
+    }
 }
@@ -0,0 +1,348 @@
+//! Checks that the programs accept a robbed account, by simulating a transfer signed by the new owner
+
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use log::info;
+use mpl_core::{
+    errors::MplCoreError,
+    types::{Key, UpdateAuthority},
+};
+use mpl_token_metadata::{
+    accounts::{MasterEdition, Metadata, TokenRecord},
+    errors::MplTokenMetadataError,
+    types::ProgrammableConfig,
+};
+use num_traits::FromPrimitive;
+use serde_json::{Value, json};
+use solana_address::Address;
+use solana_pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+};
+
+use crate::{mpl::deser_asset_header, pnft::deser_metadata, rpc::*, utils::bytes_to_b64};
+
+const AUTH_RULES_PROGRAM_ID: &str = "auth9SigNpDKz4sJJ1DfCTuZrZNSAgh9sFD3rboVmgg";
+
+/// Just what goes into a message, so instructions from the mpl-core and token-metadata builders can be mixed
+pub struct SimInstruction {
+    pub program_id: Pubkey,
+    /// pubkey, is signer, is writable
+    pub accounts: Vec<(Pubkey, bool, bool)>,
+    pub data: Vec<u8>,
+}
+
+/// Legacy transaction with zeroed signatures and blockhash, only good for `simulateTransaction` with `sigVerify: false`
+/// and `replaceRecentBlockhash: true`
+pub fn unsigned_transaction(payer: &Pubkey, instructions: &[SimInstruction]) -> Result<Vec<u8>> {
+    let address = |pubkey: &Pubkey| Address::new_from_array(pubkey.to_bytes());
+    let instructions: Vec<Instruction> = instructions
+        .iter()
+        .map(|instruction| Instruction {
+            program_id: address(&instruction.program_id),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|(pubkey, is_signer, is_writable)| AccountMeta {
+                    pubkey: address(pubkey),
+                    is_signer: *is_signer,
+                    is_writable: *is_writable,
+                })
+                .collect(),
+            data: instruction.data.clone(),
+        })
+        .collect();
+    let message = Message::new(&instructions, Some(&address(payer)));
+
+    // the signature count is a compact-u16, which is just the count below 0x80
+    let num_signers = message.header.num_required_signatures;
+    anyhow::ensure!(num_signers < 0x80, "Too many signers: {}", num_signers);
+    let mut transaction = vec![num_signers];
+    transaction.extend(std::iter::repeat_n(0, 64 * num_signers as usize));
+    transaction.extend(bincode::serialize(&message)?);
+    Ok(transaction)
+}
+
+/// Turns `{"InstructionError": [0, {"Custom": 6}]}` into the error name of the program that failed
+fn describe_error(err: &Value, program_id: &Pubkey) -> String {
+    let custom = err["InstructionError"][1]["Custom"].as_u64();
+
+    let name = custom.and_then(|code| {
+        if *program_id == mpl_core::ID {
+            MplCoreError::from_u64(code).map(|err| format!("{:?}: {}", err, err))
+        } else if *program_id == mpl_token_metadata::ID {
+            MplTokenMetadataError::from_u64(code).map(|err| format!("{:?}: {}", err, err))
+        } else {
+            None
+        }
+    });
+
+    match (name, custom) {
+        (Some(name), Some(code)) => format!("{} (0x{:x})", name, code),
+        _ => err.to_string(),
+    }
+}
+
+/// Simulates `instruction` signed by `signer` and fails with the program error if it does not go through
+async fn simulate(rpc: &Rpc, signer: &Pubkey, instruction: SimInstruction) -> Result<()> {
+    if rpc.get_account_info(&signer.to_string()).await?.is_none() {
+        anyhow::bail!(
+            "{} has no SOL to pay for the transfer, fund it with `set-balance` first",
+            signer
+        );
+    }
+
+    let program_id = instruction.program_id;
+    let transaction = unsigned_transaction(signer, &[instruction])?;
+
+    let result: WithContext<Value> = rpc
+        .call(
+            "simulateTransaction",
+            (
+                bytes_to_b64(&transaction),
+                json!({
+                    "encoding": "base64",
+                    "sigVerify": false,
+                    "replaceRecentBlockhash": true,
+                    "commitment": "processed",
+                }),
+            ),
+        )
+        .await?;
+    let value = result.value;
+
+    if let Some(logs) = value["logs"].as_array() {
+        for log in logs.iter().filter_map(Value::as_str) {
+            info!("{}", log);
+        }
+    }
+
+    if !value["err"].is_null() {
+        let logs: Vec<&str> = value["logs"]
+            .as_array()
+            .map(|logs| logs.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        anyhow::bail!(
+            "Simulated transfer failed: {}\n{}",
+            describe_error(&value["err"], &program_id),
+            logs.join("\n")
+        );
+    }
+
+    println!(
+        "Verified, {} can transfer it ({} compute units)",
+        signer,
+        value["unitsConsumed"].as_u64().unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Transfer back to the old owner that `--verify` simulates after a rob
+pub enum Verification {
+    CoreAsset {
+        asset: String,
+        new_owner: String,
+        destination: String,
+    },
+    PNft {
+        mint: String,
+        new_owner: String,
+        destination: String,
+    },
+}
+
+impl Verification {
+    pub async fn run(&self, rpc: &Rpc) -> Result<()> {
+        match self {
+            Verification::CoreAsset {
+                asset,
+                new_owner,
+                destination,
+            } => verify_core_transfer(rpc, asset, new_owner, destination).await,
+            Verification::PNft {
+                mint,
+                new_owner,
+                destination,
+            } => verify_pnft_transfer(rpc, mint, new_owner, destination).await,
+        }
+    }
+}
+
+/// Simulates the new owner of a core asset transferring it to `destination`
+pub async fn verify_core_transfer(
+    rpc: &Rpc,
+    asset_key: &str,
+    new_owner: &str,
+    destination: &str,
+) -> Result<()> {
+    let asset_key = Pubkey::from_str(asset_key)?;
+    let new_owner = Pubkey::from_str(new_owner)?;
+
+    let asset = rpc
+        .get_account_info(&asset_key.to_string())
+        .await?
+        .ok_or_else(|| anyhow!("NFT account did not exist!"))?;
+    if asset.data.first() != Some(&(Key::AssetV1 as u8)) {
+        anyhow::bail!("{} is not a core asset", asset_key);
+    }
+    let collection = match deser_asset_header(&asset.data)?.update_authority {
+        UpdateAuthority::Collection(collection) => Some(collection),
+        _ => None,
+    };
+
+    let instruction = mpl_core::instructions::TransferV1Builder::new()
+        .asset(asset_key)
+        .collection(collection)
+        .payer(new_owner)
+        .authority(Some(new_owner))
+        .new_owner(Pubkey::from_str(destination)?)
+        .instruction();
+
+    println!("Simulating TransferV1 signed by {}", new_owner);
+    simulate(
+        rpc,
+        &new_owner,
+        SimInstruction {
+            program_id: instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+                .collect(),
+            data: instruction.data,
+        },
+    )
+    .await
+}
+
+/// Simulates the new owner of a pNFT transferring it to `destination`
+pub async fn verify_pnft_transfer(
+    rpc: &Rpc,
+    mint: &str,
+    new_owner: &str,
+    destination: &str,
+) -> Result<()> {
+    let mint_addr = Address::from_str(mint)?;
+    let new_owner_addr = Address::from_str(new_owner)?;
+    let destination_addr = Address::from_str(destination)?;
+
+    let mint_key = Pubkey::new_from_array(mint_addr.to_bytes());
+    let new_owner_key = Pubkey::new_from_array(new_owner_addr.to_bytes());
+    let destination_key = Pubkey::new_from_array(destination_addr.to_bytes());
+    let token = Pubkey::new_from_array(
+        get_associated_token_address(&new_owner_addr, &mint_addr).to_bytes(),
+    );
+    let destination_token = Pubkey::new_from_array(
+        get_associated_token_address(&destination_addr, &mint_addr).to_bytes(),
+    );
+
+    let metadata_key = Metadata::find_pda(&mint_key).0;
+    let metadata = rpc
+        .get_account_info(&metadata_key.to_string())
+        .await?
+        .ok_or_else(|| anyhow!("Metadata account did not exist!"))?;
+    let rule_set = match deser_metadata(&metadata.data)?.programmable_config {
+        Some(ProgrammableConfig::V1 { rule_set }) => rule_set,
+        None => None,
+    };
+
+    let mut builder = mpl_token_metadata::instructions::TransferV1Builder::new();
+    builder
+        .token(token)
+        .token_owner(new_owner_key)
+        .destination_token(destination_token)
+        .destination_owner(destination_key)
+        .mint(mint_key)
+        .metadata(metadata_key)
+        .edition(Some(MasterEdition::find_pda(&mint_key).0))
+        .token_record(Some(TokenRecord::find_pda(&mint_key, &token).0))
+        .destination_token_record(Some(TokenRecord::find_pda(&mint_key, &destination_token).0))
+        .authority(new_owner_key)
+        .payer(new_owner_key)
+        .amount(1);
+    if let Some(rule_set) = rule_set {
+        builder
+            .authorization_rules_program(Some(Pubkey::from_str(AUTH_RULES_PROGRAM_ID)?))
+            .authorization_rules(Some(rule_set));
+    }
+    let instruction = builder.instruction();
+
+    println!(
+        "Simulating token metadata TransferV1 signed by {}",
+        new_owner
+    );
+    simulate(
+        rpc,
+        &new_owner_key,
+        SimInstruction {
+            program_id: instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+                .collect(),
+            data: instruction.data,
+        },
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_layout() {
+        let payer = Pubkey::new_from_array([1; 32]);
+        let readonly = Pubkey::new_from_array([2; 32]);
+        let writable = Pubkey::new_from_array([3; 32]);
+        let program = Pubkey::new_from_array([4; 32]);
+
+        let transaction = unsigned_transaction(
+            &payer,
+            &[SimInstruction {
+                program_id: program,
+                accounts: vec![
+                    (readonly, false, false),
+                    (writable, false, true),
+                    (payer, true, true),
+                ],
+                data: vec![7, 8],
+            }],
+        )
+        .unwrap();
+
+        // one zeroed signature
+        assert_eq!(transaction[0], 1);
+        assert!(transaction[1..65].iter().all(|&byte| byte == 0));
+
+        let message: Message = bincode::deserialize(&transaction[65..]).unwrap();
+        // 1 signer, 0 readonly signers, 2 readonly non-signers (the readonly account and the program)
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(message.header.num_readonly_signed_accounts, 0);
+        assert_eq!(message.header.num_readonly_unsigned_accounts, 2);
+        let keys: Vec<[u8; 32]> = message
+            .account_keys
+            .iter()
+            .map(|key| key.to_bytes())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                payer.to_bytes(),
+                writable.to_bytes(),
+                readonly.to_bytes(),
+                program.to_bytes()
+            ]
+        );
+
+        // one instruction calling key 3 with keys 2, 1, 0
+        assert_eq!(message.instructions.len(), 1);
+        assert_eq!(message.instructions[0].program_id_index, 3);
+        assert_eq!(message.instructions[0].accounts, vec![2, 1, 0]);
+        assert_eq!(message.instructions[0].data, vec![7, 8]);
+    }
+}
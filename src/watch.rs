@@ -0,0 +1,272 @@
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::{Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use mpl_core::types::Key;
+use reqwest::Url;
+use serde_json::{Value, json};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+use crate::{
+    pnft::{format_ata, format_metadata, format_token_record},
+    print_plugins::{format_asset_info, format_collection_info},
+    rpc::*,
+};
+
+/// Solana convention: the websocket is on the port after the HTTP one (8899 -> 8900)
+pub fn ws_url_from_http(http_url: &str) -> Result<String> {
+    let mut url = Url::parse(http_url)?;
+
+    let scheme = match url.scheme() {
+        "https" => "wss",
+        _ => "ws",
+    };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow!("Could not turn {} into a websocket url", http_url))?;
+
+    if let Some(port) = url.port() {
+        url.set_port(Some(port + 1))
+            .map_err(|_| anyhow!("Could not turn {} into a websocket url", http_url))?;
+    }
+
+    Ok(url.to_string())
+}
+
+/// Decodes the account with whatever decoder matches its owner, falling back to just the size
+pub fn format_account(account: &GetAccountInfoResponse) -> String {
+    let mut out = String::new();
+    // writing to a String can't fail
+    let _ = writeln!(out, "lamports: {}", account.lamports);
+    let _ = writeln!(out, "owner: {}", account.owner);
+    let _ = writeln!(out, "data length: {}", account.data.len());
+
+    match decode_account_data(account) {
+        Ok(Some(decoded)) => out.push_str(&decoded),
+        Ok(None) => {}
+        Err(err) => {
+            let _ = writeln!(out, "Could not decode data: {:#}", err);
+        }
+    }
+
+    out
+}
+
+fn decode_account_data(account: &GetAccountInfoResponse) -> Result<Option<String>> {
+    let data = &account.data;
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let decoded = if account.owner == mpl_core::ID.to_string() {
+        match Key::from_slice(data, 0)? {
+            Key::AssetV1 => format_asset_info(data)?,
+            Key::CollectionV1 => format_collection_info(data)?,
+            _ => return Ok(None),
+        }
+    } else if account.owner == spl_token::ID.to_string() {
+        format_ata(data)?
+    } else if account.owner == mpl_token_metadata::ID.to_string() {
+        match data[0] {
+            key if key == mpl_token_metadata::types::Key::TokenRecord as u8 => {
+                format_token_record(data)?
+            }
+            key if key == mpl_token_metadata::types::Key::MetadataV1 as u8 => {
+                format_metadata(data)?
+            }
+            _ => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(decoded))
+}
+
+/// Which lines of `new` are not in `old`. Matches lines with the longest common subsequence, so a line that is added or
+/// removed doesn't mark everything after it
+fn changed_lines(old: &[&str], new: &[&str]) -> Vec<bool> {
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changed = vec![true; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changed[j] = false;
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    changed
+}
+
+/// `new`, highlighting the lines that are not in `old`
+fn format_changes(old: Option<&str>, new: &str) -> String {
+    let new_lines: Vec<&str> = new.lines().collect();
+    let changed = match old {
+        Some(old) => changed_lines(&old.lines().collect::<Vec<_>>(), &new_lines),
+        None => vec![false; new_lines.len()],
+    };
+
+    let mut out = String::new();
+    for (line, changed) in new_lines.iter().zip(changed) {
+        if changed {
+            let _ = writeln!(out, "\x1b[1;33m* {}\x1b[0m", line);
+        } else {
+            let _ = writeln!(out, "  {}", line);
+        }
+    }
+    out
+}
+
+/// Subscribes to every account and prints each update until the websocket closes
+pub async fn watch_accounts(rpc: &Rpc, ws_url: &str, pubkeys: &[String]) -> Result<()> {
+    // what each account looked like last time, to know what changed
+    let mut last_seen: HashMap<String, String> = HashMap::new();
+
+    for (pubkey, account) in pubkeys
+        .iter()
+        .zip(rpc.get_multiple_accounts(pubkeys).await?)
+    {
+        let text = match account {
+            Some(account) => format_account(&account),
+            None => "Account does not exist\n".to_string(),
+        };
+        println!("{}:", pubkey);
+        print!("{}", format_changes(None, &text));
+        last_seen.insert(pubkey.clone(), text);
+    }
+
+    info!("Connecting to {}", ws_url);
+    let (mut ws, _) = connect_async(ws_url).await?;
+
+    for (id, pubkey) in pubkeys.iter().enumerate() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "accountSubscribe",
+            "params": [pubkey, { "encoding": "base64", "commitment": "confirmed" }],
+        });
+        ws.send(Message::Text(request.to_string().into())).await?;
+    }
+
+    // subscription id -> pubkey
+    let mut subscriptions: HashMap<u64, String> = HashMap::new();
+
+    while let Some(message) = ws.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let v: Value = serde_json::from_str(&text)?;
+
+        // answer to one of the subscribe requests
+        if let Some(id) = v.get("id").and_then(Value::as_u64) {
+            let Some(pubkey) = pubkeys.get(id as usize) else {
+                warn!("Response to a request that was never sent: {}", text);
+                continue;
+            };
+            if let Some(err) = v.get("error") {
+                let rpc_error: RpcError = serde_json::from_value(err.clone())?;
+                anyhow::bail!("Could not subscribe to {}: {}", pubkey, rpc_error);
+            }
+
+            let subscription = v
+                .get("result")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| anyhow!("Unexpected subscribe response: {}", text))?;
+            info!("Subscribed to {} ({})", pubkey, subscription);
+            subscriptions.insert(subscription, pubkey.clone());
+            continue;
+        }
+
+        if v.get("method").and_then(Value::as_str) != Some("accountNotification") {
+            warn!("Ignoring unexpected message: {}", text);
+            continue;
+        }
+
+        let params = &v["params"];
+        let Some(pubkey) = params["subscription"]
+            .as_u64()
+            .and_then(|subscription| subscriptions.get(&subscription))
+        else {
+            warn!("Notification for an unknown subscription: {}", text);
+            continue;
+        };
+
+        let value = params["result"]["value"].clone();
+        let new_text = if value.is_null() {
+            "Account does not exist\n".to_string()
+        } else {
+            format_account(&decode_ui_account(value)?)
+        };
+
+        println!("{} changed:", pubkey);
+        print!(
+            "{}",
+            format_changes(last_seen.get(pubkey).map(String::as_str), &new_text)
+        );
+        last_seen.insert(pubkey.clone(), new_text);
+    }
+
+    info!("Websocket closed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn websocket_is_on_the_next_port() {
+        assert_eq!(
+            ws_url_from_http("http://127.0.0.1:8899").unwrap(),
+            "ws://127.0.0.1:8900/"
+        );
+        assert_eq!(
+            ws_url_from_http("https://api.mainnet-beta.solana.com").unwrap(),
+            "wss://api.mainnet-beta.solana.com/"
+        );
+        assert!(ws_url_from_http("not a url").is_err());
+    }
+
+    #[test]
+    fn only_changed_lines_are_highlighted() {
+        let old = "owner: a\ndelegate: None\nstate: Initialized\n";
+        let new = "owner: a\ndelegate: Some(\n    b,\n)\nstate: Initialized\n";
+
+        let changes = format_changes(Some(old), new);
+        assert_eq!(
+            changes.lines().collect::<Vec<_>>(),
+            vec![
+                "  owner: a",
+                "\x1b[1;33m* delegate: Some(\x1b[0m",
+                "\x1b[1;33m*     b,\x1b[0m",
+                "\x1b[1;33m* )\x1b[0m",
+                "  state: Initialized",
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_lines_are_not_highlighted() {
+        assert_eq!(format_changes(None, "a\nb\n"), "  a\n  b\n");
+        assert_eq!(format_changes(Some("a\nb\n"), "a\nb\n"), "  a\n  b\n");
+    }
+}
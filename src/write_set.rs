@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{error, info};
+
+use crate::{rpc::*, snapshot::Snapshot, store::AccountStore};
+
+/// Account as it was before the write set touched it. `None` means it did not exist
+pub struct PreImage {
+    pub pubkey: String,
+    pub account: Option<GetAccountInfoResponse>,
+}
+
+/// Collects every account mutation of a command so they can all be applied together.
+/// Nothing is written until `apply`, so failing halfway through building the new accounts leaves the fork untouched,
+/// and if any write fails all the accounts are restored to what they were before
+#[derive(Default)]
+pub struct WriteSet {
+    /// pubkey and what to write to it
+    writes: Vec<(String, SetAccountInfo)>,
+}
+
+impl WriteSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes are applied in the order they are staged
+    pub fn set(&mut self, pubkey: impl ToString, info: SetAccountInfo) {
+        self.writes.push((pubkey.to_string(), info));
+    }
+
+    pub fn close(&mut self, pubkey: impl ToString) {
+        self.set(pubkey, SetAccountInfo::closed());
+    }
+
+    /// Every account touched, without repeats
+    pub fn pubkeys(&self) -> Vec<String> {
+        let mut pubkeys: Vec<String> = Vec::new();
+        for (pubkey, _) in &self.writes {
+            if !pubkeys.contains(pubkey) {
+                pubkeys.push(pubkey.clone());
+            }
+        }
+        pubkeys
+    }
+
+    /// Reads the current state of every account that is going to be touched
+    pub async fn snapshot<S: AccountStore>(&self, store: &S) -> Result<Vec<PreImage>> {
+        let pubkeys = self.pubkeys();
+        let accounts = store.get_many(&pubkeys).await?;
+
+        Ok(pubkeys
+            .into_iter()
+            .zip(accounts)
+            .map(|(pubkey, account)| PreImage { pubkey, account })
+            .collect())
+    }
+
+    /// Snapshots the original accounts, writes everything and rolls back if anything fails.
+    /// If `snapshot_path` is given the snapshot is also saved there before anything is written.
+    /// Returns the snapshot
+    pub async fn apply<S: AccountStore>(
+        &self,
+        store: &S,
+        snapshot_path: Option<&Path>,
+    ) -> Result<Vec<PreImage>> {
+        let pre_images = self.snapshot(store).await?;
+
+        if let Some(path) = snapshot_path {
+            Snapshot::from_pre_images(&pre_images).save(path)?;
+        }
+
+        info!("Writing {} accounts", self.writes.len());
+        if let Err(err) = store.set_many(&self.writes).await {
+            error!(
+                "Writing accounts failed, restoring {} accounts: {:#}",
+                pre_images.len(),
+                err
+            );
+            restore(store, &pre_images)
+                .await
+                .context("Rollback failed, the fork is in an unknown state")?;
+            return Err(err);
+        }
+
+        Ok(pre_images)
+    }
+}
+
+/// Writes the pre-images back, recreating accounts that were closed and closing accounts that did not exist.
+/// Tries every account even if some fail
+pub async fn restore<S: AccountStore>(store: &S, pre_images: &[PreImage]) -> Result<()> {
+    let mut failed = Vec::new();
+
+    for pre_image in pre_images {
+        let result = match &pre_image.account {
+            Some(account) => {
+                store
+                    .set(&pre_image.pubkey, &account.to_set_account_info()?)
+                    .await
+            }
+            None => store.close(&pre_image.pubkey).await,
+        };
+
+        if let Err(err) = result {
+            error!("Could not restore {}: {:#}", pre_image.pubkey, err);
+            failed.push(pre_image.pubkey.as_str());
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Could not restore {}", failed.join(", "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FakeAccount, FakeSurfnet, info};
+
+    #[tokio::test]
+    async fn applies_every_write_in_order() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc();
+        surfnet.set_account("old", FakeAccount::new(SYSTEM_PROGRAM_ID, vec![1]));
+
+        let mut write_set = WriteSet::new();
+        write_set.close("old");
+        write_set.set("new", info(&[2]));
+        write_set.set("new", info(&[3]));
+        let pre_images = write_set.apply(&rpc, None).await.unwrap();
+
+        assert_eq!(surfnet.account("old"), None);
+        assert_eq!(surfnet.account("new").unwrap().data, vec![3]);
+        assert_eq!(pre_images.len(), 2);
+        assert_eq!(pre_images[0].account.as_ref().unwrap().data, vec![1]);
+        assert!(pre_images[1].account.is_none());
+    }
+
+    #[tokio::test]
+    async fn failed_write_restores_every_account() {
+        let surfnet = FakeSurfnet::start().await;
+        let rpc = surfnet.rpc();
+        surfnet.set_account("closed", FakeAccount::new(SYSTEM_PROGRAM_ID, vec![1, 2, 3]));
+        surfnet.set_account("changed", FakeAccount::new(SYSTEM_PROGRAM_ID, vec![6, 7]));
+        surfnet.fail_writes_to("broken");
+
+        let mut write_set = WriteSet::new();
+        write_set.close("closed");
+        write_set.set("changed", info(&[8]));
+        write_set.set("created", info(&[4]));
+        write_set.set("broken", info(&[5]));
+
+        // restoring "broken" fails too, but everything else must still be put back
+        let Err(err) = write_set.apply(&rpc, None).await else {
+            panic!("writing to the broken account should fail");
+        };
+        assert!(format!("{:#}", err).contains("Rollback failed"));
+        assert_eq!(surfnet.account("closed").unwrap().data, vec![1, 2, 3]);
+        assert_eq!(surfnet.account("changed").unwrap().data, vec![6, 7]);
+        assert_eq!(surfnet.account("created"), None);
+    }
+}